@@ -0,0 +1,161 @@
+// Prints perft divide output for a position in the same format as Stockfish's `go perft`, so that
+// the counts can be diffed against another engine when move generation has a bug.
+//
+// Usage: perft <depth> [fen]
+// The FEN may be given as one argument or several, and defaults to the start position.
+//
+// Usage: perft --suite <file.epd> [max depth]
+// Checks every position of an EPD perft suite and reports the counts that do not match.
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+use andrews_chess_engine::board::{
+    epd::parse_perft_epd, fen_util::get_validated_game_state_from_fen, GameState,
+};
+
+const USAGE: &str = "usage: perft <depth> [fen]\n       perft --suite <file.epd> [max depth]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(suite_args) = args.strip_prefix(&["--suite".to_string()]) {
+        match parse_suite_args(suite_args) {
+            Ok((path, max_depth)) => run_suite(&path, max_depth),
+            Err(message) => exit_with_usage(&message),
+        }
+        return;
+    }
+    let (depth, mut game_state) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => exit_with_usage(&message),
+    };
+
+    let start = Instant::now();
+    let divide = game_state.perft_divide(depth);
+    let elapsed = start.elapsed();
+    print!("{}", format_divide(&divide));
+    // Timing goes to stderr so that stdout can be diffed as is
+    eprintln!("Time: {} ms", elapsed.as_millis());
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn run_suite(path: &str, max_depth: usize) {
+    let text = fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("could not read {}: {}", path, error);
+        process::exit(2);
+    });
+    let positions = parse_perft_epd(&text).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(2);
+    });
+    let start = Instant::now();
+    let mut mismatch_count = 0;
+    for position in &positions {
+        for mismatch in position.check(max_depth) {
+            println!("{}", mismatch);
+            mismatch_count += 1;
+        }
+    }
+    println!(
+        "{} positions, {} mismatches, {} ms",
+        positions.len(),
+        mismatch_count,
+        start.elapsed().as_millis()
+    );
+    if mismatch_count > 0 {
+        process::exit(1);
+    }
+}
+
+// The depth is unlimited unless given
+fn parse_suite_args(args: &[String]) -> Result<(String, usize), String> {
+    match args {
+        [path] => Ok((path.clone(), usize::MAX)),
+        [path, max_depth] => match max_depth.parse::<usize>() {
+            Ok(max_depth) if max_depth > 0 => Ok((path.clone(), max_depth)),
+            _ => Err(format!("invalid max depth '{}'", max_depth)),
+        },
+        _ => Err("--suite takes a file and an optional max depth".to_string()),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(usize, GameState), String> {
+    let Some((depth, fen)) = args.split_first() else {
+        return Err("missing depth".to_string());
+    };
+    let depth = match depth.parse::<usize>() {
+        Ok(depth) if depth > 0 => depth,
+        _ => return Err(format!("invalid depth '{}'", depth)),
+    };
+    let game_state = if fen.is_empty() {
+        GameState::default()
+    } else {
+        get_validated_game_state_from_fen(&fen.join(" ")).map_err(|error| error.to_string())?
+    };
+    Ok((depth, game_state))
+}
+
+fn format_divide(divide: &[(String, u64)]) -> String {
+    let mut output = String::new();
+    for (uci, nodes) in divide {
+        output += &format!("{}: {}\n", uci, nodes);
+    }
+    let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    output += &format!("\nNodes searched: {}\n", total);
+    output
+}
+
+#[cfg(test)]
+mod perft_tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(parse_args(&to_args(&["3"])), Ok((3, GameState::default())));
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        let expected = get_validated_game_state_from_fen(fen).unwrap();
+        assert_eq!(parse_args(&to_args(&["2", fen])), Ok((2, expected.clone())));
+        let split_fen: Vec<&str> = std::iter::once("2").chain(fen.split(' ')).collect();
+        assert_eq!(parse_args(&to_args(&split_fen)), Ok((2, expected)));
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&to_args(&["0"])).is_err());
+        assert!(parse_args(&to_args(&["x"])).is_err());
+        assert!(parse_args(&to_args(&["2", "8/8/8/8/8/8/8/8", "w"])).is_err());
+    }
+
+    #[test]
+    fn suite_args() {
+        assert_eq!(
+            parse_suite_args(&to_args(&["suite.epd"])),
+            Ok(("suite.epd".to_string(), usize::MAX))
+        );
+        assert_eq!(
+            parse_suite_args(&to_args(&["suite.epd", "4"])),
+            Ok(("suite.epd".to_string(), 4))
+        );
+        assert!(parse_suite_args(&[]).is_err());
+        assert!(parse_suite_args(&to_args(&["suite.epd", "0"])).is_err());
+        assert!(parse_suite_args(&to_args(&["suite.epd", "4", "5"])).is_err());
+    }
+
+    #[test]
+    fn divide_output() {
+        let divide = GameState::default().perft_divide(1);
+        let output = format_divide(&divide[..2]);
+        assert_eq!(output, "a2a3: 1\na2a4: 1\n\nNodes searched: 2\n");
+    }
+}
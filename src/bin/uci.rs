@@ -0,0 +1,386 @@
+// Universal Chess Interface (UCI) front end for the engine, so that it can be used from chess GUIs
+// and tools like cutechess-cli. See https://www.wbec-ridderkerk.nl/html/UCIProtocol.html
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use andrews_chess_engine::board::{
+    fen_util::{get_validated_chess960_game_state_from_fen, get_validated_game_state_from_fen},
+    types::Color,
+    GameState,
+};
+use andrews_chess_engine::engine::{
+    generate::{
+        get_evaluation, search_with_callback, Evaluation, SearchResult, MATE_SCORE, MATE_THRESHOLD,
+    },
+    limits::{Clock, SearchLimits},
+    transposition::{TranspositionTable, DEFAULT_SIZE_MB},
+};
+
+const ENGINE_NAME: &str = "Andrew's Chess Engine";
+const ENGINE_AUTHOR: &str = "Andrew Morin";
+const MAX_HASH_SIZE_MB: usize = 1024;
+
+fn main() {
+    let mut uci = Uci::default();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !uci.handle_command(&line) {
+            break;
+        }
+    }
+    uci.stop_search();
+}
+
+struct Uci {
+    game_state: GameState,
+    // Set by the UCI_Chess960 option. Positions are then read as X-FEN and castling moves are
+    // written as the king taking its own rook.
+    chess960: bool,
+    table: Arc<Mutex<TranspositionTable>>,
+    stop: Arc<AtomicBool>,
+    search_thread: Option<JoinHandle<()>>,
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Uci {
+            game_state: GameState::default(),
+            chess960: false,
+            table: Arc::new(Mutex::new(TranspositionTable::default())),
+            stop: Arc::new(AtomicBool::new(false)),
+            search_thread: None,
+        }
+    }
+}
+
+impl Uci {
+    // Returns false when the engine should exit
+    fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["uci", ..] => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_SIZE_MB, MAX_HASH_SIZE_MB
+                );
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            ["isready", ..] => println!("readyok"),
+            ["setoption", "name", "Hash", "value", size_mb, ..] => {
+                if let Ok(size_mb) = size_mb.parse::<usize>() {
+                    self.stop_search();
+                    let size_mb = size_mb.clamp(1, MAX_HASH_SIZE_MB);
+                    self.table.lock().unwrap().resize(size_mb);
+                }
+            }
+            ["setoption", "name", "UCI_Chess960", "value", value, ..] => {
+                self.chess960 = *value == "true";
+                self.game_state.chess960 = self.chess960;
+            }
+            ["ucinewgame", ..] => {
+                self.stop_search();
+                self.table.lock().unwrap().clear();
+                self.game_state = GameState {
+                    chess960: self.chess960,
+                    ..Default::default()
+                };
+            }
+            ["position", args @ ..] => match parse_position(args, self.chess960) {
+                Some(game_state) => self.game_state = game_state,
+                None => println!("info string invalid position: {}", line),
+            },
+            ["go", args @ ..] => {
+                let (limits, infinite) = parse_go(args, self.game_state.turn);
+                self.start_search(limits, infinite);
+            }
+            ["stop", ..] => self.stop_search(),
+            // Not part of UCI, for debugging the evaluation
+            ["eval", ..] => println!("{}", format_eval(&get_evaluation(&self.game_state))),
+            ["quit", ..] => return false,
+            _ => {}
+        }
+        true
+    }
+
+    fn start_search(&mut self, limits: SearchLimits, infinite: bool) {
+        self.stop_search();
+        self.stop.store(false, Ordering::Relaxed);
+        let game_state = self.game_state.clone();
+        let table = Arc::clone(&self.table);
+        let stop = Arc::clone(&self.stop);
+        self.search_thread = Some(thread::spawn(move || {
+            let mut table = table.lock().unwrap();
+            let result =
+                search_with_callback(&game_state, &limits, &mut table, &stop, |iteration| {
+                    println!("{}", format_info(iteration, &game_state));
+                });
+            // In infinite mode, the best move must not be sent until the GUI asks for it
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            match result.best_move {
+                Some(best_move) => println!("bestmove {}", game_state.move_to_uci(best_move)),
+                None => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    fn stop_search(&mut self) {
+        if let Some(search_thread) = self.search_thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            search_thread.join().unwrap();
+        }
+    }
+}
+
+// Parses the arguments of `position [startpos | fen <fen>] [moves <move>...]`
+fn parse_position(args: &[&str], chess960: bool) -> Option<GameState> {
+    let moves_index = args.iter().position(|&arg| arg == "moves");
+    let (position_args, move_args) = match moves_index {
+        Some(moves_index) => (&args[..moves_index], &args[moves_index + 1..]),
+        None => (args, &[][..]),
+    };
+    let mut game_state = match position_args {
+        ["startpos"] => GameState {
+            chess960,
+            ..Default::default()
+        },
+        ["fen", fen @ ..] if !fen.is_empty() && chess960 => {
+            get_validated_chess960_game_state_from_fen(&fen.join(" ")).ok()?
+        }
+        ["fen", fen @ ..] if !fen.is_empty() => {
+            get_validated_game_state_from_fen(&fen.join(" ")).ok()?
+        }
+        _ => return None,
+    };
+    for move_arg in move_args {
+        let next_move = game_state.parse_uci_move(move_arg).ok()?;
+        game_state = game_state.perform_move(next_move);
+    }
+    Some(game_state)
+}
+
+// Parses the arguments of `go`. Returns the search limits and whether the search is infinite.
+fn parse_go(args: &[&str], turn: Color) -> (SearchLimits, bool) {
+    let mut limits = SearchLimits::default();
+    let mut infinite = false;
+    let mut white_clock = Clock::default();
+    let mut black_clock = Clock::default();
+    let mut has_clock = false;
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut next_value = || args.next().and_then(|value| value.parse::<u64>().ok());
+        match arg {
+            "wtime" => {
+                white_clock.remaining = next_value().unwrap_or(0);
+                has_clock |= turn == Color::White;
+            }
+            "btime" => {
+                black_clock.remaining = next_value().unwrap_or(0);
+                has_clock |= turn == Color::Black;
+            }
+            "winc" => white_clock.increment = next_value().unwrap_or(0),
+            "binc" => black_clock.increment = next_value().unwrap_or(0),
+            "movetime" => limits.move_time = next_value(),
+            "depth" => limits.max_depth = next_value().map(|depth| depth as u32),
+            "nodes" => limits.max_nodes = next_value(),
+            "infinite" => infinite = true,
+            _ => {}
+        }
+    }
+    if has_clock && !infinite {
+        limits.clock = Some(match turn {
+            Color::Black => black_clock,
+            _ => white_clock,
+        });
+    }
+    (limits, infinite)
+}
+
+fn format_info(result: &SearchResult, game_state: &GameState) -> String {
+    let nps = result.nodes * 1000 / result.time_ms.max(1);
+    // Chess960 castling moves can only be written out in the position they are played from
+    let mut pv_state = game_state.clone();
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|&next_move| {
+            let uci = pv_state.move_to_uci(next_move);
+            pv_state.make_move(next_move);
+            uci
+        })
+        .collect();
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        format_score(result.score),
+        result.nodes,
+        nps,
+        result.time_ms,
+        pv.join(" ")
+    )
+}
+
+fn format_eval(evaluation: &Evaluation) -> String {
+    format!(
+        "info string phase {} middlegame {} endgame {} score {}",
+        evaluation.phase, evaluation.middlegame, evaluation.endgame, evaluation.score
+    )
+}
+
+// Mate scores are reported in moves rather than plies, negative if the engine is getting mated
+fn format_score(score: i32) -> String {
+    if score > MATE_THRESHOLD {
+        format!("mate {}", (MATE_SCORE - score + 1) / 2)
+    } else if score < -MATE_THRESHOLD {
+        format!("mate -{}", (MATE_SCORE + score) / 2)
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+#[cfg(test)]
+mod uci_tests {
+    use super::*;
+    use andrews_chess_engine::board::{fen_util::get_game_state_from_fen, types::Piece};
+
+    #[test]
+    fn eval() {
+        let evaluation = Evaluation {
+            phase: 24,
+            middlegame: 30,
+            endgame: -10,
+            score: 30,
+        };
+        assert_eq!(
+            format_eval(&evaluation),
+            "info string phase 24 middlegame 30 endgame -10 score 30"
+        );
+    }
+
+    #[test]
+    fn position_illegal_fen() {
+        assert_eq!(
+            parse_position(
+                &["fen", "8/8/8/8/8/8/8/4K3", "w", "-", "-", "0", "1"],
+                false
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn position_startpos() {
+        assert_eq!(
+            parse_position(&["startpos"], false),
+            Some(GameState::default())
+        );
+    }
+
+    #[test]
+    fn position_startpos_moves() {
+        let game_state =
+            parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"], false).unwrap();
+        let expected = get_game_state_from_fen(
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+        )
+        .unwrap();
+        assert_eq!(game_state.board, expected.board);
+        assert_eq!(game_state.hash, expected.hash);
+    }
+
+    #[test]
+    fn position_fen_moves() {
+        let args = "fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1 e8c8"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let game_state = parse_position(&args, false).unwrap();
+        let expected = get_game_state_from_fen("2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2").unwrap();
+        assert_eq!(game_state.board, expected.board);
+        assert_eq!(game_state.hash, expected.hash);
+    }
+
+    #[test]
+    fn position_promotion() {
+        let args = "fen 8/P6k/8/8/8/8/8/K7 w - - 0 1 moves a7a8n"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let game_state = parse_position(&args, false).unwrap();
+        assert_eq!(
+            game_state.board.get_square(0),
+            (Color::White, Piece::Knight)
+        );
+    }
+
+    #[test]
+    fn position_chess960_castling() {
+        let args = "fen r3k2r/8/8/8/8/8/8/1R2K1R1 w KQkq - 0 1 moves e1g1 e8a8"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        assert_eq!(parse_position(&args, false), None);
+        let game_state = parse_position(&args, true).unwrap();
+        let expected = get_game_state_from_fen("2kr3r/8/8/8/8/8/8/1R3RK1 w - - 2 2").unwrap();
+        assert_eq!(game_state.board, expected.board);
+        assert_eq!(game_state.hash, expected.hash);
+    }
+
+    #[test]
+    fn position_invalid() {
+        assert_eq!(parse_position(&[], false), None);
+        assert_eq!(parse_position(&["fen"], false), None);
+        assert_eq!(parse_position(&["fen", "8/8/8", "w"], false), None);
+        assert_eq!(parse_position(&["startpos", "moves", "e2e5"], false), None);
+    }
+
+    #[test]
+    fn go_clock_for_side_to_move() {
+        let args = [
+            "wtime", "60000", "btime", "30000", "winc", "1000", "binc", "500",
+        ];
+        let (limits, infinite) = parse_go(&args, Color::Black);
+        assert!(!infinite);
+        assert_eq!(
+            limits.clock,
+            Some(Clock {
+                remaining: 30000,
+                increment: 500
+            })
+        );
+    }
+
+    #[test]
+    fn go_limits() {
+        let args = ["depth", "6", "nodes", "10000", "movetime", "250"];
+        let (limits, infinite) = parse_go(&args, Color::White);
+        assert!(!infinite);
+        assert_eq!(limits.max_depth, Some(6));
+        assert_eq!(limits.max_nodes, Some(10000));
+        assert_eq!(limits.move_time, Some(250));
+        assert_eq!(limits.clock, None);
+    }
+
+    #[test]
+    fn go_infinite() {
+        let (limits, infinite) = parse_go(&["infinite"], Color::White);
+        assert!(infinite);
+        assert_eq!(limits, SearchLimits::default());
+    }
+
+    #[test]
+    fn mate_scores() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        assert_eq!(format_score(MATE_SCORE - 1), "mate 1");
+        assert_eq!(format_score(MATE_SCORE - 3), "mate 2");
+        assert_eq!(format_score(-MATE_SCORE + 2), "mate -1");
+        assert_eq!(format_score(-MATE_SCORE + 4), "mate -2");
+    }
+}
@@ -0,0 +1,148 @@
+use super::{fen_util::get_chess960_game_state_from_fen, GameState};
+
+pub const CHESS960_POSITION_COUNT: usize = 960;
+
+// Knight placements on the five squares left after the bishops and queen, in Scharnagl order
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// The Chess960 start position with the given Scharnagl number, from 0 (BBQNNRKR) to 959
+// (RKRNNQBB). Number 518 is the standard start position.
+pub fn get_chess960_game_state(index: usize) -> Option<GameState> {
+    let back_rank = get_chess960_back_rank(index)?;
+    let fen = format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        back_rank.to_ascii_lowercase(),
+        back_rank
+    );
+    Some(get_chess960_game_state_from_fen(&fen).expect("every Chess960 start position is valid"))
+}
+
+// White's pieces from the a-file to the h-file, e.g. RNBQKBNR for number 518
+fn get_chess960_back_rank(index: usize) -> Option<String> {
+    if index >= CHESS960_POSITION_COUNT {
+        return None;
+    }
+    let mut back_rank = [' '; 8];
+    // The first bishop goes on a light square (b, d, f or h) and the second on a dark one
+    let mut remainder = index;
+    back_rank[remainder % 4 * 2 + 1] = 'B';
+    remainder /= 4;
+    back_rank[remainder % 4 * 2] = 'B';
+    remainder /= 4;
+    place_on_nth_empty(&mut back_rank, remainder % 6, 'Q');
+    remainder /= 6;
+    // Placing the second knight first keeps the first knight's empty square count unchanged
+    let (first_knight, second_knight) = KNIGHT_PLACEMENTS[remainder];
+    place_on_nth_empty(&mut back_rank, second_knight, 'N');
+    place_on_nth_empty(&mut back_rank, first_knight, 'N');
+    // The king always goes between the rooks
+    for piece in ['R', 'K', 'R'] {
+        place_on_nth_empty(&mut back_rank, 0, piece);
+    }
+    Some(back_rank.iter().collect())
+}
+
+fn place_on_nth_empty(back_rank: &mut [char; 8], n: usize, piece: char) {
+    let file = (0..8)
+        .filter(|&file| back_rank[file] == ' ')
+        .nth(n)
+        .expect("enough empty squares");
+    back_rank[file] = piece;
+}
+
+#[cfg(test)]
+mod chess960_tests {
+    use super::super::epd::{parse_perft_epd, run_perft_suite, run_perft_suite_from_env};
+    use super::super::fen_util::game_state_to_shredder_fen_string;
+    use super::*;
+    use std::collections::HashSet;
+
+    // The first positions of the published Chess960 perft results, with their published counts.
+    // Castling rights are given in Shredder-FEN.
+    const CHESS960_PERFT_SUITE: &str = include_str!("test_data/chess960_perft.epd");
+
+    // Hand-made castling cases rather than published results: kings that start on their castled
+    // square, castling rooks that stand on the king's destination or are pinned to the king, and
+    // both sides castling on either wing. The counts agree between this engine and a separately
+    // written perft.
+    const CHESS960_CASTLING_CASES: &str = include_str!("test_data/chess960_castling.epd");
+
+    #[test]
+    fn back_ranks() {
+        assert_eq!(get_chess960_back_rank(0).unwrap(), "BBQNNRKR");
+        assert_eq!(get_chess960_back_rank(518).unwrap(), "RNBQKBNR");
+        assert_eq!(get_chess960_back_rank(959).unwrap(), "RKRNNQBB");
+        assert_eq!(get_chess960_back_rank(960), None);
+    }
+
+    #[test]
+    fn standard_start_position() {
+        let game_state = get_chess960_game_state(518).unwrap();
+        assert!(game_state.chess960);
+        assert_eq!(game_state.board, GameState::default().board);
+        assert_eq!(game_state.castle, GameState::default().castle);
+        assert_eq!(game_state.hash, GameState::default().hash);
+    }
+
+    #[test]
+    fn start_positions_are_distinct_and_valid() {
+        let mut back_ranks = HashSet::new();
+        for index in 0..CHESS960_POSITION_COUNT {
+            let game_state = get_chess960_game_state(index).unwrap();
+            assert_eq!(game_state.validate(), vec![], "{}", index);
+            let back_rank = get_chess960_back_rank(index).unwrap();
+            // Bishops on opposite colors
+            let bishop_files: Vec<usize> = back_rank.match_indices('B').map(|(i, _)| i).collect();
+            assert_ne!(bishop_files[0] % 2, bishop_files[1] % 2, "{}", back_rank);
+            assert!(back_ranks.insert(back_rank));
+        }
+    }
+
+    #[test]
+    fn start_position_fen() {
+        let mut game_state = get_chess960_game_state(0).unwrap();
+        assert_eq!(
+            game_state_to_shredder_fen_string(&game_state),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+        );
+        assert_eq!(game_state.perft(1), 20);
+    }
+
+    #[test]
+    fn chess960_perft_suite_shallow() {
+        let positions = parse_perft_epd(CHESS960_PERFT_SUITE).unwrap();
+        assert!(positions
+            .iter()
+            .all(|position| position.game_state.chess960));
+        assert_eq!(run_perft_suite(&positions, 3), vec![]);
+    }
+
+    #[test]
+    fn chess960_castling_perft() {
+        let positions = parse_perft_epd(CHESS960_CASTLING_CASES).unwrap();
+        assert!(positions
+            .iter()
+            .all(|position| position.game_state.chess960));
+        assert_eq!(run_perft_suite(&positions, usize::MAX), vec![]);
+    }
+
+    // Takes minutes in a release build. Run with
+    // PERFT_SUITE_DEPTH=4 cargo test --release chess960_perft_suite_full -- --ignored
+    // to limit the depth, which is unlimited by default.
+    #[test]
+    #[ignore]
+    fn chess960_perft_suite_full() {
+        run_perft_suite_from_env(CHESS960_PERFT_SUITE);
+    }
+}
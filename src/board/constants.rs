@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 pub const MAILBOX: [Option<usize>; 120] = [
     None,
     None,
@@ -202,3 +204,362 @@ const fn build_slide_attack_bitmasks(offset_directions: [usize; 2]) -> [[u64; 4]
     }
     bitmasks
 }
+
+// Fancy magic bitboards for sliding piece attacks. The occupied squares that can block a slider are
+// multiplied by a magic number, and the top bits of the product index a table of attack sets built
+// on first use. The magic numbers were found by random search for this board's indexing (a8 = 0)
+// and are checked to index every occupancy without collisions when the tables are built.
+const ROOK_MAGICS: [u64; 64] = [
+    0x2080_0018_2040_0080,
+    0xc440_0020_0040_1000,
+    0x8100_1040_0820_0100,
+    0x1100_1001_0004_2008,
+    0x0100_0402_0800_1100,
+    0x0500_0221_0008_0400,
+    0x4880_2100_0080_0200,
+    0x3080_0040_2100_0080,
+    0x8011_8001_2040_0080,
+    0x0841_4002_2010_0040,
+    0x0080_8080_1000_2000,
+    0x0840_8010_0008_0480,
+    0x0601_0008_0010_0500,
+    0x0212_0004_0811_0200,
+    0x0201_0002_0001_0004,
+    0x01a4_8000_4100_0880,
+    0x4001_0100_2040_8000,
+    0x6020_0440_2040_1001,
+    0x2420_8180_1000_6001,
+    0x0800_2200_0a00_4010,
+    0x0200_8080_0400_0800,
+    0x2042_0080_8004_0002,
+    0x2804_0400_0810_0102,
+    0x0000_0200_0041_00a4,
+    0x0040_0424_8000_4090,
+    0x1520_1000_4000_2040,
+    0xc000_2001_0040_1100,
+    0x6000_8802_8010_0080,
+    0x0842_0801_0010_0500,
+    0x2000_0400_8080_0200,
+    0xa000_8204_0008_0110,
+    0x0000_8020_8000_4100,
+    0x0800_8841_0200_2200,
+    0x9000_8445_0200_2200,
+    0x0150_0080_1080_2000,
+    0x0010_0008_0080_8010,
+    0x4010_8008_0080_0401,
+    0x0100_0400_8080_0200,
+    0x4402_0008_0200_0104,
+    0x0000_4044_1200_2081,
+    0x0480_0080_4101_0027,
+    0xa020_0050_0024_4008,
+    0x8008_1100_2001_0040,
+    0x2350_0100_8008_0800,
+    0x0184_0080_0800_8004,
+    0x300a_0008_0401_0100,
+    0x2000_4802_01a4_0010,
+    0x4010_1410_4082_0011,
+    0x0060_8102_0020_4600,
+    0x1012_0080_4110_2200,
+    0x0000_2000_1000_8080,
+    0x3208_0080_0810_0080,
+    0x0401_0010_0408_0100,
+    0x4000_4010_2004_0801,
+    0x1410_0801_0210_0400,
+    0x0004_0080_4104_0200,
+    0x0204_6b00_5040_8003,
+    0x8202_4000_8100_102b,
+    0x1411_8108_4012_0122,
+    0x4751_0008_1000_0421,
+    0x0022_0004_0810_2002,
+    0x0083_0004_0046_0829,
+    0x4080_080a_1003_5584,
+    0x0243_0010_2200_8841,
+];
+const BISHOP_MAGICS: [u64; 64] = [
+    0x04c9_1004_8811_0020,
+    0xa044_3002_0061_0400,
+    0x0004_0808_8104_3008,
+    0x0024_0502_0010_0861,
+    0x180c_5040_3421_0600,
+    0x5008_9024_2024_0000,
+    0x010a_020a_2006_9128,
+    0x0208_2021_0808_4000,
+    0x0820_0420_8802_4488,
+    0x0200_4830_00a2_0050,
+    0x8002_2208_0100_2800,
+    0x0220_208a_0080_d0e6,
+    0xd101_1405_2000_0041,
+    0x0080_0088_0441_0400,
+    0x8000_0200_8a20_7010,
+    0x0000_0144_0c88_4800,
+    0x0008_10c0_4284_0404,
+    0x0002_8010_6202_4400,
+    0x8e01_1210_0400_2042,
+    0x0030_2001_0400_8110,
+    0x4124_0082_0121_0a02,
+    0x0202_0117_4050_0c02,
+    0x0010_8051_1088_2008,
+    0x0020_82b2_00a4_0108,
+    0x00c4_4100_1050_0128,
+    0x0408_0202_2104_0103,
+    0xa021_1005_8104_0620,
+    0x0000_8481_0802_0040,
+    0x00a1_0040_0400_4040,
+    0x2010_2300_0080_80a0,
+    0xa008_2208_0100_8211,
+    0x4300_a100_4421_0820,
+    0x0808_0210_3508_6004,
+    0x4480_d004_2310_8408,
+    0x0001_4410_0442_0122,
+    0xc002_0100_4004_0040,
+    0x0004_0802_0000_2008,
+    0x0132_0081_0002_0040,
+    0x2002_4831_0000_4400,
+    0x0010_9220_4430_8400,
+    0x2010_8804_4004_1000,
+    0x0002_0201_0404_6050,
+    0x4001_0029_0100_1010,
+    0x0204_8c22_0420_4800,
+    0x0010_0801_0040_4400,
+    0x3010_0200_8300_080a,
+    0x8010_8208_2440_611a,
+    0x00a4_0805_4148_0104,
+    0x8030_8808_8290_0108,
+    0x0007_0088_0c12_d084,
+    0x0062_0102_8844_0400,
+    0x0002_008a_20a8_0080,
+    0x4000_1210_8208_8040,
+    0x0120_0420_0421_0020,
+    0x4892_2008_0100_4002,
+    0x0220_0240_8105_0000,
+    0x0020_1400_8804_1002,
+    0x0822_0500_4510_2803,
+    0x0100_5080_2608_1200,
+    0x0400_0808_a210_4404,
+    0x8420_40d1_4004_8840,
+    0x0000_0008_2004_0420,
+    0x0105_5051_0911_0400,
+    0x0040_1401_0041_0b09,
+];
+
+// The squares whose occupancy changes a slider's attacks: every ray without its last square, since
+// a piece on the edge of the board blocks nothing beyond it
+pub static ROOK_RELEVANT_BITMASKS: [u64; 64] =
+    build_relevant_bitmasks(CARDINAL_MAILBOX_DIRECTION_OFFSETS);
+pub static BISHOP_RELEVANT_BITMASKS: [u64; 64] =
+    build_relevant_bitmasks(DIAGONAL_MAILBOX_DIRECTION_OFFSETS);
+
+static ROOK_MAGIC_TABLE: OnceLock<MagicTable> = OnceLock::new();
+static BISHOP_MAGIC_TABLE: OnceLock<MagicTable> = OnceLock::new();
+
+pub fn get_rook_attack_bitmask(index: usize, occupied: u64) -> u64 {
+    ROOK_MAGIC_TABLE
+        .get_or_init(|| {
+            MagicTable::new(
+                &CARDINAL_ATTACK_BITMASKS,
+                &ROOK_RELEVANT_BITMASKS,
+                &ROOK_MAGICS,
+            )
+        })
+        .get_attack_bitmask(index, occupied)
+}
+
+pub fn get_bishop_attack_bitmask(index: usize, occupied: u64) -> u64 {
+    BISHOP_MAGIC_TABLE
+        .get_or_init(|| {
+            MagicTable::new(
+                &DIAGONAL_ATTACK_BITMASKS,
+                &BISHOP_RELEVANT_BITMASKS,
+                &BISHOP_MAGICS,
+            )
+        })
+        .get_attack_bitmask(index, occupied)
+}
+
+struct MagicTable {
+    relevant_bitmasks: [u64; 64],
+    magics: [u64; 64],
+    shifts: [u32; 64],
+    // Where each square's attack sets start in `attack_bitmasks`
+    offsets: [usize; 64],
+    attack_bitmasks: Vec<u64>,
+}
+
+impl MagicTable {
+    fn new(
+        ray_bitmasks: &[[u64; 4]; 64],
+        relevant_bitmasks: &[u64; 64],
+        magics: &[u64; 64],
+    ) -> MagicTable {
+        let mut shifts = [0; 64];
+        let mut offsets = [0; 64];
+        let mut attack_bitmasks = vec![];
+        for index in 0..64 {
+            let relevant_bitmask = relevant_bitmasks[index];
+            shifts[index] = 64 - relevant_bitmask.count_ones();
+            offsets[index] = attack_bitmasks.len();
+            attack_bitmasks.resize(
+                attack_bitmasks.len() + (1 << relevant_bitmask.count_ones()),
+                0,
+            );
+            // Visit every subset of the relevant squares with the Carry-Rippler trick
+            let mut occupied: u64 = 0;
+            loop {
+                let attack_bitmask = get_ray_attack_bitmask(ray_bitmasks, index, occupied);
+                let magic_index = offsets[index]
+                    + (occupied.wrapping_mul(magics[index]) >> shifts[index]) as usize;
+                let entry = &mut attack_bitmasks[magic_index];
+                assert!(
+                    *entry == 0 || *entry == attack_bitmask,
+                    "magic number for index {} has a collision",
+                    index
+                );
+                *entry = attack_bitmask;
+                occupied = occupied.wrapping_sub(relevant_bitmask) & relevant_bitmask;
+                if occupied == 0 {
+                    break;
+                }
+            }
+        }
+        MagicTable {
+            relevant_bitmasks: *relevant_bitmasks,
+            magics: *magics,
+            shifts,
+            offsets,
+            attack_bitmasks,
+        }
+    }
+
+    fn get_attack_bitmask(&self, index: usize, occupied: u64) -> u64 {
+        let occupied = occupied & self.relevant_bitmasks[index];
+        let magic_index =
+            (occupied.wrapping_mul(self.magics[index]) >> self.shifts[index]) as usize;
+        self.attack_bitmasks[self.offsets[index] + magic_index]
+    }
+}
+
+// Slow attack generation for building the magic tables. Each ray stops at the nearest occupied
+// square, which is the highest bit for rays towards a8 and the lowest bit for rays towards h1.
+fn get_ray_attack_bitmask(ray_bitmasks: &[[u64; 4]; 64], index: usize, occupied: u64) -> u64 {
+    let mut attack_bitmask = 0;
+    for (direction, ray_bitmask) in ray_bitmasks[index].iter().enumerate() {
+        let blockers = ray_bitmask & occupied;
+        attack_bitmask |= if blockers == 0 {
+            *ray_bitmask
+        } else {
+            let blocker_index = if direction < 2 {
+                63 - blockers.leading_zeros() as usize
+            } else {
+                blockers.trailing_zeros() as usize
+            };
+            ray_bitmask ^ ray_bitmasks[blocker_index][direction]
+        };
+    }
+    attack_bitmask
+}
+
+const fn build_relevant_bitmasks(offset_directions: [usize; 2]) -> [u64; 64] {
+    let ray_bitmasks = build_slide_attack_bitmasks(offset_directions);
+    let mut bitmasks: [u64; 64] = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut direction = 0;
+        while direction < 4 {
+            let ray_bitmask = ray_bitmasks[index][direction];
+            if ray_bitmask != 0 {
+                // The last square is the lowest bit for rays towards a8 and the highest towards h1
+                let last_square_bitmask = if direction < 2 {
+                    ray_bitmask & ray_bitmask.wrapping_neg()
+                } else {
+                    1 << (63 - ray_bitmask.leading_zeros())
+                };
+                bitmasks[index] |= ray_bitmask ^ last_square_bitmask;
+            }
+            direction += 1;
+        }
+        index += 1;
+    }
+    bitmasks
+}
+
+// Random keys for Zobrist hashing, generated at compile time with a fixed seed so that hashes are
+// stable between builds. Piece keys are indexed by (color - 1) * 6 + (piece - 1), then board index.
+pub static ZOBRIST_PIECE_KEYS: [[u64; 64]; 12] = build_zobrist_piece_keys(0x5eed_0001);
+pub static ZOBRIST_CASTLE_KEYS: [u64; 4] = build_zobrist_keys::<4>(0x5eed_0002);
+pub static ZOBRIST_EN_PASSANT_KEYS: [u64; 8] = build_zobrist_keys::<8>(0x5eed_0003);
+pub const ZOBRIST_BLACK_TO_MOVE_KEY: u64 = splitmix64(0x5eed_0004).1;
+
+// https://prng.di.unimi.it/splitmix64.c
+// Returns the next state and the generated value
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn build_zobrist_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys: [u64; N] = [0; N];
+    let mut state = seed;
+    let mut index = 0;
+    while index < N {
+        let (next_state, key) = splitmix64(state);
+        keys[index] = key;
+        state = next_state;
+        index += 1;
+    }
+    keys
+}
+
+const fn build_zobrist_piece_keys(seed: u64) -> [[u64; 64]; 12] {
+    let mut keys: [[u64; 64]; 12] = [[0; 64]; 12];
+    let mut piece_index = 0;
+    while piece_index < 12 {
+        keys[piece_index] = build_zobrist_keys::<64>(seed.wrapping_mul(piece_index as u64 + 1));
+        piece_index += 1;
+    }
+    keys
+}
+
+#[cfg(test)]
+mod constants_tests {
+    use super::*;
+
+    #[test]
+    fn magic_attacks_match_ray_attacks() {
+        // Random sparse and dense occupancies, from the same generator as the Zobrist keys
+        let occupancies: Vec<u64> = build_zobrist_keys::<64>(0x5eed_0006)
+            .into_iter()
+            .flat_map(|key| [key, key & key.rotate_left(17), key | key.rotate_left(29)])
+            .chain([0, u64::MAX])
+            .collect();
+        for index in 0..64 {
+            for &occupied in &occupancies {
+                assert_eq!(
+                    get_rook_attack_bitmask(index, occupied),
+                    get_ray_attack_bitmask(&CARDINAL_ATTACK_BITMASKS, index, occupied)
+                );
+                assert_eq!(
+                    get_bishop_attack_bitmask(index, occupied),
+                    get_ray_attack_bitmask(&DIAGONAL_ATTACK_BITMASKS, index, occupied)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn slider_attacks() {
+        // Rook on d4 with blockers on d6 and b4
+        let occupied = 1 << 19 | 1 << 33;
+        let attacks = [19, 27, 33, 34, 36, 37, 38, 39, 43, 51, 59];
+        assert_eq!(
+            get_rook_attack_bitmask(35, occupied),
+            attacks
+                .iter()
+                .fold(0, |bitmask, index| bitmask | 1 << index)
+        );
+        // Bishop on a8 blocked on c6
+        assert_eq!(get_bishop_attack_bitmask(0, 1 << 18), 1 << 9 | 1 << 18);
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+use super::{
+    fen_util::{get_validated_game_state_from_fen, FenError},
+    GameState,
+};
+
+// A position from a perft suite in EPD format, one position per line with the expected counts as
+// operations after the FEN, e.g.
+// rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400 ;D3 8902
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerftPosition {
+    pub fen: String,
+    pub game_state: GameState,
+    // Depth and expected node count, in the order given
+    pub expected: Vec<(usize, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpdError {
+    // Line numbers start at 1
+    InvalidFen { line: usize, error: FenError },
+    InvalidOperation { line: usize, operation: String },
+}
+
+impl Display for EpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpdError::InvalidFen { line, error } => write!(f, "line {}: {}", line, error),
+            EpdError::InvalidOperation { line, operation } => {
+                write!(f, "line {}: invalid perft operation '{}'", line, operation)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerftMismatch {
+    pub fen: String,
+    pub depth: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl Display for PerftMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: depth {} expected {} nodes but found {}",
+            self.fen, self.depth, self.expected, self.actual
+        )
+    }
+}
+
+impl PerftPosition {
+    // Runs perft to every expected depth up to `max_depth`
+    pub fn check(&self, max_depth: usize) -> Vec<PerftMismatch> {
+        let mut game_state = self.game_state.clone();
+        self.expected
+            .iter()
+            .filter(|&&(depth, _)| depth <= max_depth)
+            .filter_map(|&(depth, expected)| {
+                let actual = game_state.perft(depth);
+                (actual != expected).then(|| PerftMismatch {
+                    fen: self.fen.clone(),
+                    depth,
+                    expected,
+                    actual,
+                })
+            })
+            .collect()
+    }
+}
+
+// Blank lines and lines starting with '#' are skipped
+pub fn parse_perft_epd(text: &str) -> Result<Vec<PerftPosition>, EpdError> {
+    let mut positions = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(';');
+        let fen = fields.next().unwrap_or_default().trim();
+        let game_state =
+            get_validated_game_state_from_fen(fen).map_err(|error| EpdError::InvalidFen {
+                line: line_number,
+                error,
+            })?;
+        let expected = fields
+            .map(|operation| {
+                parse_perft_operation(operation.trim()).ok_or_else(|| EpdError::InvalidOperation {
+                    line: line_number,
+                    operation: operation.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        positions.push(PerftPosition {
+            fen: fen.to_string(),
+            game_state,
+            expected,
+        });
+    }
+    Ok(positions)
+}
+
+// Parses an operation like "D3 8902" into the depth and node count
+fn parse_perft_operation(operation: &str) -> Option<(usize, u64)> {
+    let (depth, nodes) = operation.strip_prefix('D')?.split_once(' ')?;
+    Some((depth.parse().ok()?, nodes.trim().parse().ok()?))
+}
+
+pub fn run_perft_suite(positions: &[PerftPosition], max_depth: usize) -> Vec<PerftMismatch> {
+    positions
+        .iter()
+        .flat_map(|position| position.check(max_depth))
+        .collect()
+}
+
+// Checks a whole suite for the ignored full suite tests, up to the depth in the PERFT_SUITE_DEPTH
+// environment variable if it is set, and panics after printing every mismatch
+#[cfg(test)]
+pub(crate) fn run_perft_suite_from_env(text: &str) {
+    let max_depth = std::env::var("PERFT_SUITE_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(usize::MAX);
+    let positions = parse_perft_epd(text).unwrap();
+    let mismatches = run_perft_suite(&positions, max_depth);
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    assert!(mismatches.is_empty(), "{} mismatches", mismatches.len());
+}
+
+#[cfg(test)]
+mod epd_tests {
+    use super::*;
+
+    // Positions with promotions, en passant captures that are legal or expose the king, castling
+    // that is available or blocked by attacks, and checks. The counts come from an independent
+    // move generator, and each position goes to the first depth above 20,000 nodes.
+    const PERFT_SUITE: &str = include_str!("test_data/perft_suite.epd");
+
+    #[test]
+    fn parse() {
+        let positions = parse_perft_epd(
+            "# comment\n\
+             \n\
+             rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400\n\
+             8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 ;D1 15\n",
+        )
+        .unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].game_state, GameState::default());
+        assert_eq!(positions[0].expected, vec![(1, 20), (2, 400)]);
+        assert_eq!(positions[1].fen, "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3");
+        assert_eq!(positions[1].expected, vec![(1, 15)]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            parse_perft_epd("\n8/8/8/8/8/8/8/8 w - - ;D1 0"),
+            Err(EpdError::InvalidFen { line: 2, .. })
+        ));
+        assert_eq!(
+            parse_perft_epd("4k3/8/8/8/8/8/8/4K3 w - - ;D1 5 ;X2 3"),
+            Err(EpdError::InvalidOperation {
+                line: 1,
+                operation: "X2 3".to_string()
+            })
+        );
+        assert!(parse_perft_epd("4k3/8/8/8/8/8/8/4K3 w - - ;D1").is_err());
+    }
+
+    #[test]
+    fn mismatches() {
+        let positions =
+            parse_perft_epd("4k3/8/8/8/8/8/8/4K3 w - - 0 1 ;D1 5 ;D2 25 ;D3 1").unwrap();
+        assert_eq!(run_perft_suite(&positions, 2), vec![]);
+        let mismatches = run_perft_suite(&positions, 3);
+        assert_eq!(
+            mismatches,
+            vec![PerftMismatch {
+                fen: "4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string(),
+                depth: 3,
+                expected: 1,
+                actual: 170
+            }]
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1: depth 3 expected 1 nodes but found 170"
+        );
+    }
+
+    #[test]
+    fn perft_suite_shallow() {
+        let positions = parse_perft_epd(PERFT_SUITE).unwrap();
+        assert!(positions.len() > 250);
+        assert_eq!(run_perft_suite(&positions, 2), vec![]);
+    }
+
+    // Takes minutes in a debug build. Run with
+    // PERFT_SUITE_DEPTH=4 cargo test --release perft_suite_full -- --ignored
+    // to limit the depth, which is unlimited by default.
+    #[test]
+    #[ignore]
+    fn perft_suite_full() {
+        run_perft_suite_from_env(PERFT_SUITE);
+    }
+}
@@ -1,213 +1,299 @@
+use std::fmt::Display;
+
+use super::validation::PositionProblem;
 use super::*;
 
-pub fn get_game_state_from_fen(fen: &str) -> GameState {
-    let mut board = Board::default();
-    let mut castle = CastleAvailability {
-        white_kingside: false,
-        white_queenside: false,
-        black_kingside: false,
-        black_queenside: false,
-    };
-    let mut index: usize = 0;
-    let mut chars = fen.chars();
-    let mut found_slash = true;
-    loop {
-        let c = chars.next();
-        if c.is_none() {
-            panic!("Invalid FEN: '{}', ended too early", fen);
+// Every error carries the byte offset into the FEN string where parsing failed
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    // The FEN ended before the side to move
+    UnexpectedEnd { offset: usize },
+    // A field separator or trailing field that does not belong there
+    UnexpectedCharacter { offset: usize, found: char },
+    InvalidPiece { offset: usize, found: char },
+    RankTooShort { offset: usize },
+    RankTooLong { offset: usize },
+    WrongRankCount { offset: usize },
+    InvalidSideToMove { offset: usize },
+    InvalidCastling { offset: usize },
+    InvalidEnPassant { offset: usize },
+    InvalidHalfmoveCounter { offset: usize },
+    InvalidFullmoveNumber { offset: usize },
+    // The FEN is well formed but describes a position that can not occur, see GameState::validate.
+    // This is about the position as a whole, so there is no offset.
+    IllegalPosition { problems: Vec<PositionProblem> },
+}
+
+impl FenError {
+    // The byte in the FEN where parsing failed, if the error is about a single field
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            FenError::UnexpectedEnd { offset }
+            | FenError::UnexpectedCharacter { offset, .. }
+            | FenError::InvalidPiece { offset, .. }
+            | FenError::RankTooShort { offset }
+            | FenError::RankTooLong { offset }
+            | FenError::WrongRankCount { offset }
+            | FenError::InvalidSideToMove { offset }
+            | FenError::InvalidCastling { offset }
+            | FenError::InvalidEnPassant { offset }
+            | FenError::InvalidHalfmoveCounter { offset }
+            | FenError::InvalidFullmoveNumber { offset } => Some(*offset),
+            FenError::IllegalPosition { .. } => None,
         }
-        let c = c.unwrap();
-        let digit = c.to_digit(10);
-        if let Some(digit) = digit {
-            let end_index = index + digit as usize;
-            for i in index..end_index {
-                board.clear_square(i);
+    }
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            FenError::UnexpectedEnd { .. } => "ended too early".to_string(),
+            FenError::UnexpectedCharacter { found, .. } => {
+                format!("unexpected character '{}'", found)
             }
-            index = end_index;
-        } else {
-            if c == ' ' {
-                if index != 64 {
-                    panic!("Invalid FEN: '{}', board ended too early", fen);
-                }
-                break;
-            } else if c == '/' {
-                if !index.is_multiple_of(8) {
-                    panic!(
-                        "Invalid FEN: '{}', rank was too short at index {}",
-                        fen, index
-                    );
-                }
-                found_slash = true;
-                continue;
-            } else if index.is_multiple_of(8) && !found_slash {
-                panic!(
-                    "Invalid FEN: '{}', rank was too long at index {}",
-                    fen, index
-                );
+            FenError::InvalidPiece { found, .. } => format!("invalid piece '{}'", found),
+            FenError::RankTooShort { .. } => "rank has fewer than 8 squares".to_string(),
+            FenError::RankTooLong { .. } => "rank has more than 8 squares".to_string(),
+            FenError::WrongRankCount { .. } => "board does not have 8 ranks".to_string(),
+            FenError::InvalidSideToMove { .. } => "side to move must be w or b".to_string(),
+            FenError::InvalidCastling { .. } => "invalid castling availability".to_string(),
+            FenError::InvalidEnPassant { .. } => "invalid en passant target square".to_string(),
+            FenError::InvalidHalfmoveCounter { .. } => "invalid halfmove counter".to_string(),
+            FenError::InvalidFullmoveNumber { .. } => "invalid fullmove number".to_string(),
+            FenError::IllegalPosition { problems } => {
+                let problems: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                format!("illegal position: {}", problems.join(", "))
             }
-            found_slash = false;
-            let color = if c.is_uppercase() {
-                Color::White
-            } else {
-                Color::Black
-            };
-            let piece = match c.to_ascii_lowercase() {
-                'p' => Piece::Pawn,
-                'b' => Piece::Bishop,
-                'n' => Piece::Knight,
-                'r' => Piece::Rook,
-                'q' => Piece::Queen,
-                'k' => Piece::King,
-                _ => panic!("Invalid FEN: '{}', invalid character '{}'", fen, c),
-            };
-            board.update_square(index, color, piece);
-            index += 1
-        }
-    }
-    let active_color = chars.next();
-    let turn = match active_color {
-        Some('w') => Color::White,
-        Some('b') => Color::Black,
-        Some(c) => panic!("Invalid FEN: '{}', invalid active color '{}'", fen, c),
-        None => panic!("Invalid FEN: '{}', ended too early", fen),
-    };
-    let c = chars.next();
-    if c.is_none() {
-        return GameState {
-            board,
-            turn,
-            castle,
-            ..Default::default()
         };
-    }
-    let c = c.unwrap();
-    if c != ' ' {
-        panic!("Invalid FEN: '{}', invalid character '{}'", fen, c);
-    }
-
-    let mut c = chars.next();
-    if Some('-') != c {
-        while c.is_some() {
-            let castle_char = c.unwrap();
-
-            match castle_char {
-                'K' => castle.white_kingside = true,
-                'Q' => castle.white_queenside = true,
-                'k' => castle.black_kingside = true,
-                'q' => castle.black_queenside = true,
-                ' ' => break,
-                _ => panic!(
-                    "Invalid FEN: '{}', invalid character '{}'",
-                    fen, castle_char
-                ),
-            };
-
-            c = chars.next();
-        }
-    } else {
-        c = chars.next();
-        if c.is_none() {
-            return GameState {
-                board,
-                turn,
-                castle,
-                ..Default::default()
-            };
-        }
-        let c = c.unwrap();
-        if c != ' ' {
-            panic!("Invalid FEN: '{}', invalid character '{}'", fen, c);
+        match self.offset() {
+            Some(offset) => write!(f, "invalid FEN at byte {}: {}", offset, description),
+            None => write!(f, "invalid FEN: {}", description),
         }
     }
+}
 
-    let c = chars.next();
-    if c.is_none() {
-        return GameState {
-            board,
-            turn,
-            castle,
-            ..Default::default()
-        };
-    }
+impl std::error::Error for FenError {}
 
-    let c = c.unwrap();
-    let mut en_passant_index = None;
-    if c != '-' {
-        let file = c;
-        if !('a'..='h').contains(&file) {
-            panic!(
-                "Invalid FEN: '{}', invalid character in en passant target square '{}'",
-                fen, c
-            )
-        };
-        let rank = chars.next();
-        if rank.is_none() {
-            panic!(
-                "Invalid FEN: '{}', en passant target square not complete",
-                fen
-            );
-        }
-        let rank = rank.unwrap();
-        if !('1'..='8').contains(&rank) {
-            panic!(
-                "Invalid FEN: '{}', invalid character in en passant target square '{}'",
-                fen, c
-            )
-        };
+// Castling letters KQkq mean the rooks on the h- and a-files. Shredder-FEN castling fields, which
+// name the rook files instead, e.g. HAha, make the position a Chess960 one.
+pub fn get_game_state_from_fen(fen: &str) -> Result<GameState, FenError> {
+    let mut game_state = parse_fen(fen, false)?;
+    game_state.hash = game_state.compute_hash();
+    Ok(game_state)
+}
 
-        en_passant_index = Some(get_index_from_square(file, rank));
+// For Chess960 positions in X-FEN, where KQkq mean the outermost rook on each side of the king,
+// and Shredder-FEN
+pub fn get_chess960_game_state_from_fen(fen: &str) -> Result<GameState, FenError> {
+    let mut game_state = parse_fen(fen, true)?;
+    game_state.hash = game_state.compute_hash();
+    Ok(game_state)
+}
+
+// Like get_game_state_from_fen, but also rejects positions that GameState::validate finds problems
+// with, so the result is safe to generate moves and search from
+pub fn get_validated_game_state_from_fen(fen: &str) -> Result<GameState, FenError> {
+    validated(get_game_state_from_fen(fen)?)
+}
+
+pub fn get_validated_chess960_game_state_from_fen(fen: &str) -> Result<GameState, FenError> {
+    validated(get_chess960_game_state_from_fen(fen)?)
+}
+
+fn validated(game_state: GameState) -> Result<GameState, FenError> {
+    let problems = game_state.validate();
+    if problems.is_empty() {
+        Ok(game_state)
+    } else {
+        Err(FenError::IllegalPosition { problems })
     }
+}
 
-    let c = chars.next();
-    if c.is_none() {
-        return GameState {
-            board,
-            turn,
-            castle,
-            ..Default::default()
-        };
+// Only the board and side to move are required. The castling, en passant and counter fields may be
+// left off the end, in which case they default to no castling, no en passant square and zero.
+fn parse_fen(fen: &str, mut chess960: bool) -> Result<GameState, FenError> {
+    let fen = fen.trim_end();
+    let mut fields = Vec::new();
+    let mut offset = 0;
+    for field in fen.split(' ') {
+        if field.is_empty() && offset < fen.len() {
+            return Err(FenError::UnexpectedCharacter { offset, found: ' ' });
+        }
+        fields.push((offset, field));
+        offset += field.len() + 1;
     }
 
-    let c = c.unwrap();
-    if c != ' ' {
-        panic!("Invalid FEN: '{}', invalid character '{}'", fen, c);
+    let (board_offset, board_field) = fields[0];
+    let board = parse_board(board_field, board_offset)?;
+
+    let turn = match fields.get(1) {
+        Some((_, "w")) => Color::White,
+        Some((_, "b")) => Color::Black,
+        Some(&(offset, _)) => return Err(FenError::InvalidSideToMove { offset }),
+        None => return Err(FenError::UnexpectedEnd { offset: fen.len() }),
+    };
+
+    let mut castle = CastleAvailability::none();
+    let mut has_rook_files = false;
+    if let Some(&(offset, castle_field)) = fields.get(2) {
+        if castle_field != "-" {
+            for (i, c) in castle_field.char_indices() {
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                let (kingside, rook_file) = match c.to_ascii_lowercase() {
+                    'k' if chess960 => (true, find_outermost_rook_file(&board, color, true)),
+                    'q' if chess960 => (false, find_outermost_rook_file(&board, color, false)),
+                    'k' => (true, 7),
+                    'q' => (false, 0),
+                    file @ 'a'..='h' => {
+                        has_rook_files = true;
+                        let rook_file = file as usize - 'a' as usize;
+                        // Without a king on the back rank, which validation reports, any side will do
+                        let king_file = find_back_rank_king_file(&board, color).unwrap_or(4);
+                        (rook_file > king_file, rook_file)
+                    }
+                    _ => return Err(FenError::InvalidCastling { offset: offset + i }),
+                };
+                let right = castle.get_mut(color, kingside);
+                if right.is_some() {
+                    return Err(FenError::InvalidCastling { offset: offset + i });
+                }
+                *right = Some(rook_file);
+            }
+        }
     }
+    chess960 |= has_rook_files;
 
-    let mut int_str = chars.next().unwrap().to_string();
-    for c in chars {
-        if c == ' ' {
-            break;
-        } else if !c.is_ascii_digit() {
-            panic!(
-                "Invalid FEN: '{}', invalid character in halfmove counter '{}'",
-                fen, c
-            );
+    let mut en_passant_index = None;
+    if let Some(&(offset, en_passant_field)) = fields.get(3) {
+        if en_passant_field != "-" {
+            let chars: Vec<char> = en_passant_field.chars().collect();
+            match chars.as_slice() {
+                [file @ 'a'..='h', rank @ ('3' | '6')] => {
+                    en_passant_index = Some(get_index_from_square(*file, *rank))
+                }
+                _ => return Err(FenError::InvalidEnPassant { offset }),
+            }
         }
-        int_str.push(c);
     }
 
-    dbg!("{}", &int_str);
+    let mut halfmove_counter = 0;
+    if let Some(&(offset, halfmove_field)) = fields.get(4) {
+        halfmove_counter = match halfmove_field.parse::<u16>() {
+            Ok(halfmove_counter) => halfmove_counter,
+            Err(_) => return Err(FenError::InvalidHalfmoveCounter { offset }),
+        };
+    }
 
-    let halfmove_counter = int_str.parse::<u8>();
-    if halfmove_counter.is_err() {
-        panic!("Invalid FEN: '{}', halfmove counter is invalid.", fen);
+    let mut fullmove_number = 1;
+    if let Some(&(offset, fullmove_field)) = fields.get(5) {
+        fullmove_number = match fullmove_field.parse::<u32>() {
+            Ok(fullmove_number) if fullmove_number > 0 => fullmove_number,
+            _ => return Err(FenError::InvalidFullmoveNumber { offset }),
+        };
     }
 
-    let halfmove_counter = halfmove_counter.unwrap();
-    if halfmove_counter > 100 {
-        panic!("Invalid FEN: '{}', halfmove counter is too large.", fen);
+    if let Some(&(offset, field)) = fields.get(6) {
+        let found = field.chars().next().unwrap_or(' ');
+        return Err(FenError::UnexpectedCharacter { offset, found });
     }
 
-    GameState {
+    Ok(GameState {
         board,
         turn,
         castle,
         en_passant_index,
         halfmove_counter,
+        fullmove_number,
+        chess960,
+        ..Default::default()
+    })
+}
+
+fn find_back_rank_king_file(board: &Board, color: Color) -> Option<usize> {
+    let back_rank_start = get_back_rank_start(color);
+    (0..8).find(|&file| board.get_square(back_rank_start + file) == (color, Piece::King))
+}
+
+// X-FEN's K and Q stand for the rook furthest from the king on that side. Falls back to the h- or
+// a-file, for validation to report, if there is no such rook.
+fn find_outermost_rook_file(board: &Board, color: Color, kingside: bool) -> usize {
+    let back_rank_start = get_back_rank_start(color);
+    let is_rook = |file: &usize| board.get_square(back_rank_start + file) == (color, Piece::Rook);
+    let found = match find_back_rank_king_file(board, color) {
+        Some(king_file) if kingside => (king_file + 1..8).rev().find(is_rook),
+        Some(king_file) => (0..king_file).find(is_rook),
+        None => None,
+    };
+    found.unwrap_or(if kingside { 7 } else { 0 })
+}
+
+fn parse_board(board_field: &str, board_offset: usize) -> Result<Board, FenError> {
+    let mut board = Board::default();
+    let mut index: usize = 0;
+    let mut rank_squares = 0;
+    let mut rank_count = 1;
+    for (i, c) in board_field.char_indices() {
+        let offset = board_offset + i;
+        match c {
+            '/' => {
+                if rank_squares < 8 {
+                    return Err(FenError::RankTooShort { offset });
+                }
+                if rank_count == 8 {
+                    return Err(FenError::WrongRankCount { offset });
+                }
+                rank_squares = 0;
+                rank_count += 1;
+            }
+            '1'..='8' => {
+                let empty_squares = c.to_digit(10).unwrap() as usize;
+                if rank_squares + empty_squares > 8 {
+                    return Err(FenError::RankTooLong { offset });
+                }
+                for i in index..index + empty_squares {
+                    board.clear_square(i);
+                }
+                index += empty_squares;
+                rank_squares += empty_squares;
+            }
+            _ => {
+                let color = if c.is_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => Piece::Pawn,
+                    'b' => Piece::Bishop,
+                    'n' => Piece::Knight,
+                    'r' => Piece::Rook,
+                    'q' => Piece::Queen,
+                    'k' => Piece::King,
+                    _ => return Err(FenError::InvalidPiece { offset, found: c }),
+                };
+                if rank_squares == 8 {
+                    return Err(FenError::RankTooLong { offset });
+                }
+                board.update_square(index, color, piece);
+                index += 1;
+                rank_squares += 1;
+            }
+        }
+    }
+    let offset = board_offset + board_field.len();
+    if rank_squares < 8 {
+        return Err(FenError::RankTooShort { offset });
     }
+    if rank_count < 8 {
+        return Err(FenError::WrongRankCount { offset });
+    }
+    Ok(board)
 }
 
-#[allow(dead_code)]
 pub fn get_square_from_index(index: usize) -> String {
     let file = match index % 8 {
         0 => 'a',
@@ -242,40 +328,65 @@ pub fn get_index_from_square(file: char, rank: char) -> usize {
     unreachable!()
 }
 
-#[allow(dead_code)]
-fn game_state_to_fen_string(game_state: &GameState) -> String {
+// Chess960 castling rights are written in X-FEN, which is the same as standard FEN unless a right
+// uses a rook other than the outermost one
+pub fn game_state_to_fen_string(game_state: &GameState) -> String {
+    game_state_to_fen_string_inner(game_state, false)
+}
+
+// Like game_state_to_fen_string, but with every castling right written as the rook's file, e.g.
+// HAha for the standard start position
+pub fn game_state_to_shredder_fen_string(game_state: &GameState) -> String {
+    game_state_to_fen_string_inner(game_state, true)
+}
+
+fn game_state_to_fen_string_inner(game_state: &GameState, shredder: bool) -> String {
     let board = board_to_fen_string(&game_state.board);
     let turn = match game_state.turn {
         Color::Black => 'b',
         _ => 'w',
     };
-    let castle = castle_availability_to_fen(&game_state.castle);
+    let castle = castle_availability_to_fen(game_state, shredder);
     let en_passant_square = if let Some(index) = game_state.en_passant_index {
         get_square_from_index(index)
     } else {
         "-".to_string()
     };
-    // let fullmove_counter = game_state.move_list.len() / 2;
     format!(
         "{} {} {} {} {} {}",
-        board, turn, castle, en_passant_square, game_state.halfmove_counter, 0
+        board,
+        turn,
+        castle,
+        en_passant_square,
+        game_state.halfmove_counter,
+        game_state.fullmove_number
     )
 }
 
-#[allow(dead_code)]
-fn castle_availability_to_fen(castle_availability: &CastleAvailability) -> String {
+fn castle_availability_to_fen(game_state: &GameState, shredder: bool) -> String {
     let mut output = String::new();
-    if castle_availability.white_kingside {
-        output.push('K');
-    }
-    if castle_availability.white_queenside {
-        output.push('Q');
-    }
-    if castle_availability.black_kingside {
-        output.push('k');
-    }
-    if castle_availability.black_queenside {
-        output.push('q');
+    for (color, kingside, letter) in [
+        (Color::White, true, 'K'),
+        (Color::White, false, 'Q'),
+        (Color::Black, true, 'k'),
+        (Color::Black, false, 'q'),
+    ] {
+        let Some(rook_index) = game_state.castle.get_rook_index(color, kingside) else {
+            continue;
+        };
+        let rook_file = rook_index % 8;
+        let is_outermost = !game_state.chess960
+            || find_outermost_rook_file(&game_state.board, color, kingside) == rook_file;
+        if shredder || !is_outermost {
+            let file_letter = (b'a' + rook_file as u8) as char;
+            output.push(if color == Color::White {
+                file_letter.to_ascii_uppercase()
+            } else {
+                file_letter
+            });
+        } else {
+            output.push(letter);
+        }
     }
 
     if output.is_empty() {
@@ -285,7 +396,6 @@ fn castle_availability_to_fen(castle_availability: &CastleAvailability) -> Strin
     output
 }
 
-#[allow(dead_code)]
 fn board_to_fen_string(board: &Board) -> String {
     let mut board_str = String::new();
     let mut space_count = 0;
@@ -301,13 +411,15 @@ fn board_to_fen_string(board: &Board) -> String {
             board_str.push(letter);
         }
 
-        // last square in the rank, but not last rank
-        if index % 8 == 7 && index < 63 {
+        // last square in the rank
+        if index % 8 == 7 {
             if space_count > 0 {
                 board_str.push_str(&space_count.to_string());
                 space_count = 0;
             }
-            board_str.push('/');
+            if index < 63 {
+                board_str.push('/');
+            }
         }
     }
 
@@ -350,7 +462,8 @@ mod tests {
     fn board_to_fen() {
         let game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq e3 12",
-        );
+        )
+        .unwrap();
         let fen = board_to_fen_string(&game_state.board);
 
         assert_eq!(
@@ -363,12 +476,13 @@ mod tests {
     fn game_state_to_fen() {
         let game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq e3 12",
-        );
+        )
+        .unwrap();
         let fen = game_state_to_fen_string(&game_state);
 
         assert_eq!(
             fen,
-            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq e3 12 0"
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq e3 12 1"
         );
     }
 
@@ -376,12 +490,14 @@ mod tests {
     fn en_passant_index() {
         let game_state = get_game_state_from_fen(
             "rnbqkb1r/1pp2ppp/p3pn2/3p4/8/P3PN2/1PPPBPPP/RNBQK2R w KQkq d6 0 5",
-        );
+        )
+        .unwrap();
         assert_eq!(game_state.en_passant_index, Some(19));
 
         let game_state = get_game_state_from_fen(
             "rnbqkb1r/1pp2ppp/p3pn2/3p4/8/P3PN2/1PPPBPPP/RNBQK2R w KQkq - 0 5",
-        );
+        )
+        .unwrap();
         assert_eq!(game_state.en_passant_index, None);
     }
 
@@ -389,7 +505,211 @@ mod tests {
     fn halfmove_counter() {
         let game_state = get_game_state_from_fen(
             "rnbqkb1r/1pp2ppp/p3pn2/3p4/8/P3PN2/1PPPBPPP/RNBQK2R w KQkq - 2 5",
-        );
+        )
+        .unwrap();
         assert_eq!(game_state.halfmove_counter, 2);
+        // Games only end automatically after 75 moves, so counters past 100 are legal
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 149 105").unwrap();
+        assert_eq!(game_state.halfmove_counter, 149);
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "rnbqkb1r/pppppppp/5n2/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq d3 0 2",
+            "4k3/8/8/8/8/8/8/7K b - - 99 150",
+        ] {
+            let game_state = get_game_state_from_fen(fen).unwrap();
+            assert_eq!(game_state_to_fen_string(&game_state), fen);
+        }
+    }
+
+    #[test]
+    fn chess960_castling_fields() {
+        let shredder = "1r2k1r1/8/8/8/8/8/8/RR2K2R w HBg - 0 1";
+        let game_state = get_game_state_from_fen(shredder).unwrap();
+        assert!(game_state.chess960);
+        assert_eq!(
+            game_state.castle,
+            CastleAvailability {
+                white_kingside: Some(7),
+                white_queenside: Some(1),
+                black_kingside: Some(6),
+                black_queenside: None,
+            }
+        );
+        // X-FEN only names the file when the rook is not the outermost one
+        assert_eq!(
+            game_state_to_fen_string(&game_state),
+            "1r2k1r1/8/8/8/8/8/8/RR2K2R w KBk - 0 1"
+        );
+        assert_eq!(game_state_to_shredder_fen_string(&game_state), shredder);
+        let x_fen = get_chess960_game_state_from_fen("1r2k1r1/8/8/8/8/8/8/RR2K2R w KBk - 0 1");
+        assert_eq!(x_fen, Ok(game_state));
+
+        let standard = get_game_state_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(!standard.chess960);
+        assert_eq!(standard.castle, CastleAvailability::default());
+        assert_eq!(
+            game_state_to_shredder_fen_string(&standard),
+            "r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1"
+        );
+        assert_eq!(
+            get_game_state_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1").map(|g| g.castle),
+            Ok(CastleAvailability::default())
+        );
+    }
+
+    #[test]
+    fn fullmove_number() {
+        let game_state = get_game_state_from_fen(
+            "rnbqkb1r/1pp2ppp/p3pn2/3p4/8/P3PN2/1PPPBPPP/RNBQK2R w KQkq - 2 5",
+        )
+        .unwrap();
+        assert_eq!(game_state.fullmove_number, 5);
+        let game_state = game_state.perform_move(Move::castle(60, 62));
+        assert_eq!(game_state.fullmove_number, 5);
+        let game_state = game_state.perform_move(Move::new(5, 19));
+        assert_eq!(game_state.fullmove_number, 6);
+        assert_eq!(
+            game_state_to_fen_string(&game_state),
+            "rnbqk2r/1pp2ppp/p2bpn2/3p4/8/P3PN2/1PPPBPPP/RNBQ1RK1 w kq - 4 6"
+        );
+    }
+
+    #[test]
+    fn optional_fields() {
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/4K3 b").unwrap();
+        assert_eq!(game_state.turn, Color::Black);
+        assert_eq!(game_state.castle, CastleAvailability::none());
+        assert_eq!(game_state.en_passant_index, None);
+        assert_eq!(game_state.halfmove_counter, 0);
+        assert_eq!(game_state.fullmove_number, 1);
+        assert!(get_game_state_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1\n").is_ok());
+    }
+
+    #[test]
+    fn board_errors() {
+        assert_eq!(
+            get_game_state_from_fen("rnbqkbnr/ppxppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"),
+            Err(FenError::InvalidPiece {
+                offset: 11,
+                found: 'x'
+            })
+        );
+        assert_eq!(
+            get_game_state_from_fen("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"),
+            Err(FenError::RankTooShort { offset: 16 })
+        );
+        assert_eq!(
+            get_game_state_from_fen("rnbqkbnr/pppppppp/72/8/8/8/PPPPPPPP/RNBQKBNR w"),
+            Err(FenError::RankTooLong { offset: 19 })
+        );
+        assert_eq!(
+            get_game_state_from_fen("rnbqkbnrp/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"),
+            Err(FenError::RankTooLong { offset: 8 })
+        );
+        assert_eq!(
+            get_game_state_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w"),
+            Err(FenError::WrongRankCount { offset: 34 })
+        );
+        assert_eq!(
+            get_game_state_from_fen("8/8/8/8/8/8/8/8/8 w"),
+            Err(FenError::WrongRankCount { offset: 15 })
+        );
+    }
+
+    #[test]
+    fn field_errors() {
+        let board = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+        let cases = [
+            ("", FenError::UnexpectedEnd { offset: 43 }),
+            (
+                "  w",
+                FenError::UnexpectedCharacter {
+                    offset: 44,
+                    found: ' ',
+                },
+            ),
+            (" x KQkq - 0 1", FenError::InvalidSideToMove { offset: 44 }),
+            (" w KQxq - 0 1", FenError::InvalidCastling { offset: 48 }),
+            (" w KQkK - 0 1", FenError::InvalidCastling { offset: 49 }),
+            (" w HK - 0 1", FenError::InvalidCastling { offset: 47 }),
+            (" w Ki - 0 1", FenError::InvalidCastling { offset: 47 }),
+            (" w KQkq e4 0 1", FenError::InvalidEnPassant { offset: 51 }),
+            (" w KQkq e33 0 1", FenError::InvalidEnPassant { offset: 51 }),
+            (
+                " w KQkq - 65536 1",
+                FenError::InvalidHalfmoveCounter { offset: 53 },
+            ),
+            (
+                " w KQkq - -1 1",
+                FenError::InvalidHalfmoveCounter { offset: 53 },
+            ),
+            (
+                " w KQkq - 0 0",
+                FenError::InvalidFullmoveNumber { offset: 55 },
+            ),
+            (
+                " w KQkq - 0 1 e",
+                FenError::UnexpectedCharacter {
+                    offset: 57,
+                    found: 'e',
+                },
+            ),
+        ];
+        for (fields, error) in cases {
+            let fen = board.to_string() + fields;
+            assert_eq!(get_game_state_from_fen(&fen), Err(error), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn error_message() {
+        let error =
+            get_game_state_from_fen("rnbqkbnr/ppxppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid FEN at byte 11: invalid piece 'x'"
+        );
+    }
+
+    #[test]
+    fn validated() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(
+            get_validated_game_state_from_fen(fen),
+            get_game_state_from_fen(fen)
+        );
+        let error = get_validated_game_state_from_fen("8/8/8/8/8/8/8/4K2p w k - 0 1").unwrap_err();
+        assert_eq!(
+            error,
+            FenError::IllegalPosition {
+                problems: vec![
+                    PositionProblem::MissingKing(Color::Black),
+                    PositionProblem::PawnOnBackRank(63),
+                    PositionProblem::CastlingWithoutKing {
+                        color: Color::Black,
+                        kingside: true
+                    },
+                    PositionProblem::CastlingWithoutRook {
+                        color: Color::Black,
+                        kingside: true
+                    },
+                ]
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid FEN: illegal position: black has no king, pawn on back rank at h1, \
+             black can castle kingside but the king is not on its starting square, \
+             black can castle kingside but the rook is not on its starting square"
+        );
     }
 }
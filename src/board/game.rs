@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+
+use super::{types::*, GameState};
+
+// Squares where (rank + file) is even, starting with a8
+const LIGHT_SQUARES: u64 = 0xaa_55_aa_55_aa_55_aa_55;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum GameStatus {
+    Ongoing,
+    // The color that delivered checkmate
+    Checkmate(Color),
+    Stalemate,
+    // Draws a player may claim
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    // Draws that end the game automatically
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+}
+
+impl GameStatus {
+    // Claimable draws only end the game once a player claims them
+    pub fn is_game_over(&self) -> bool {
+        *self != GameStatus::Ongoing && !self.is_claimable_draw()
+    }
+
+    pub fn is_claimable_draw(&self) -> bool {
+        matches!(
+            self,
+            GameStatus::FiftyMoveRule | GameStatus::ThreefoldRepetition
+        )
+    }
+}
+
+// A game from its start position, with the position after every move so that repetitions can be
+// detected. Moves that are taken back are kept until a different move is played, so that they can
+// be replayed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "GameData")]
+pub struct Game {
+    start: GameState,
+    moves: Vec<Move>,
+    states: Vec<GameState>,
+    // The number of moves currently played, which is less than the number of moves after undoing
+    ply: usize,
+}
+
+// The fields of a serialized Game, checked before they become one
+#[derive(Deserialize)]
+struct GameData {
+    start: GameState,
+    moves: Vec<Move>,
+    states: Vec<GameState>,
+    ply: usize,
+}
+
+impl TryFrom<GameData> for Game {
+    type Error = String;
+
+    fn try_from(data: GameData) -> Result<Self, Self::Error> {
+        if data.states.len() != data.moves.len() {
+            return Err(format!(
+                "{} moves but {} positions",
+                data.moves.len(),
+                data.states.len()
+            ));
+        }
+        if data.ply > data.moves.len() {
+            return Err(format!(
+                "ply {} is past the last of {} moves",
+                data.ply,
+                data.moves.len()
+            ));
+        }
+        Ok(Game {
+            start: data.start,
+            moves: data.moves,
+            states: data.states,
+            ply: data.ply,
+        })
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(GameState::default())
+    }
+}
+
+impl Game {
+    pub fn new(start: GameState) -> Game {
+        Game {
+            start,
+            moves: Vec::new(),
+            states: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    pub fn start(&self) -> &GameState {
+        &self.start
+    }
+
+    // The moves leading to the current position
+    pub fn moves(&self) -> &[Move] {
+        &self.moves[..self.ply]
+    }
+
+    // Every move, including ones that were taken back and can be redone
+    pub fn all_moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn current(&self) -> &GameState {
+        match self.ply {
+            0 => &self.start,
+            ply => &self.states[ply - 1],
+        }
+    }
+
+    // Assumes the move is legal in the current position, like GameState::perform_move. Playing the
+    // move that was taken back is the same as redoing it, any other move replaces the moves after
+    // the current position.
+    pub fn play_move(&mut self, next_move: Move) {
+        if self.moves.get(self.ply) == Some(&next_move) {
+            self.ply += 1;
+            return;
+        }
+        let next_state = self.current().perform_move(next_move);
+        self.moves.truncate(self.ply);
+        self.states.truncate(self.ply);
+        self.moves.push(next_move);
+        self.states.push(next_state);
+        self.ply += 1;
+    }
+
+    // Takes back the last move, if there is one
+    pub fn undo_move(&mut self) -> Option<Move> {
+        self.ply = self.ply.checked_sub(1)?;
+        Some(self.moves[self.ply])
+    }
+
+    // Plays the last move that was taken back again, if there is one
+    pub fn redo_move(&mut self) -> Option<Move> {
+        let next_move = *self.moves.get(self.ply)?;
+        self.ply += 1;
+        Some(next_move)
+    }
+
+    // Goes to the position after the given number of moves, where 0 is the start position. Returns
+    // false if there are not that many moves.
+    pub fn go_to_ply(&mut self, ply: usize) -> bool {
+        if ply > self.moves.len() {
+            return false;
+        }
+        self.ply = ply;
+        true
+    }
+
+    // Checkmate and stalemate take precedence over the draw rules, since the move that ends the
+    // game that way stands even if it is also the 50th or 75th move
+    pub fn status(&self) -> GameStatus {
+        let current = self.current();
+        if current.generate_legal_moves().is_empty() {
+            return if current.is_in_check() {
+                GameStatus::Checkmate(current.turn.opposite())
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        let repetitions = self.count_repetitions();
+        if current.is_insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else if repetitions >= 5 {
+            GameStatus::FivefoldRepetition
+        } else if current.halfmove_counter >= 150 {
+            GameStatus::SeventyFiveMoveRule
+        } else if repetitions >= 3 {
+            GameStatus::ThreefoldRepetition
+        } else if current.halfmove_counter >= 100 {
+            GameStatus::FiftyMoveRule
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    // How many times the current position has occurred, including now. Captures and pawn moves can
+    // not be undone, so only positions since the last one need to be compared.
+    fn count_repetitions(&self) -> usize {
+        let current = self.current();
+        std::iter::once(&self.start)
+            .chain(self.states[..self.ply].iter())
+            .rev()
+            .take(current.halfmove_counter as usize + 1)
+            .filter(|game_state| game_state.hash == current.hash)
+            .count()
+    }
+}
+
+impl GameState {
+    // Positions where neither side can checkmate by any sequence of legal moves: king against king
+    // with at most one minor piece, or only bishops that all stand on the same square color
+    pub fn is_insufficient_material(&self) -> bool {
+        let board = &self.board;
+        let heavy_pieces_and_pawns = board.get_piece_bitmask(Piece::Pawn)
+            | board.get_piece_bitmask(Piece::Rook)
+            | board.get_piece_bitmask(Piece::Queen);
+        if heavy_pieces_and_pawns != 0 {
+            return false;
+        }
+        let knights = board.get_piece_bitmask(Piece::Knight);
+        let bishops = board.get_piece_bitmask(Piece::Bishop);
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+}
+
+#[cfg(test)]
+mod game_tests {
+    use super::super::fen_util::get_game_state_from_fen;
+    use super::*;
+
+    fn play_san_moves(start: GameState, moves: &[&str]) -> Game {
+        let mut game = Game::new(start);
+        for san in moves {
+            let next_move = game.current().parse_san(san).unwrap();
+            game.play_move(next_move);
+        }
+        game
+    }
+
+    #[test]
+    fn ongoing() {
+        let game = play_san_moves(GameState::default(), &["e4", "e5"]);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        assert!(!game.status().is_game_over());
+        assert_eq!(game.moves().len(), 2);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = play_san_moves(GameState::default(), &["e4", "e5"]);
+        let after_e4 = GameState::default().perform_move(game.moves()[0]);
+        assert_eq!(game.redo_move(), None);
+        assert_eq!(game.undo_move(), Some(Move::two_square_pawn_move(12, 28)));
+        assert_eq!(game.ply(), 1);
+        assert_eq!(game.moves().len(), 1);
+        assert_eq!(game.all_moves().len(), 2);
+        assert_eq!(game.current(), &after_e4);
+        assert!(game.undo_move().is_some());
+        assert_eq!(game.current(), &GameState::default());
+        assert_eq!(game.undo_move(), None);
+        assert_eq!(game.redo_move(), Some(Move::two_square_pawn_move(52, 36)));
+        assert_eq!(game.current(), &after_e4);
+        assert!(game.redo_move().is_some());
+        assert_eq!(game, play_san_moves(GameState::default(), &["e4", "e5"]));
+    }
+
+    #[test]
+    fn go_to_ply() {
+        let mut game = play_san_moves(GameState::default(), &["e4", "e5", "Nf3"]);
+        assert!(game.go_to_ply(0));
+        assert_eq!(game.current(), &GameState::default());
+        assert!(game.go_to_ply(2));
+        assert_eq!(game.current().turn, Color::White);
+        assert_eq!(game.moves().len(), 2);
+        assert!(!game.go_to_ply(4));
+        assert_eq!(game.ply(), 2);
+        assert!(game.go_to_ply(3));
+        assert_eq!(game.moves().len(), 3);
+    }
+
+    #[test]
+    fn new_move_replaces_undone_moves() {
+        let mut game = play_san_moves(GameState::default(), &["e4", "e5", "Nf3"]);
+        game.go_to_ply(1);
+        // Replaying the same move keeps the rest of the game
+        let e5 = game.current().parse_san("e5").unwrap();
+        game.play_move(e5);
+        assert_eq!(game.all_moves().len(), 3);
+        assert_eq!(game.redo_move(), game.all_moves().last().copied());
+
+        game.go_to_ply(1);
+        let c5 = game.current().parse_san("c5").unwrap();
+        game.play_move(c5);
+        assert_eq!(game.all_moves().len(), 2);
+        assert_eq!(game.redo_move(), None);
+        assert_eq!(game, play_san_moves(GameState::default(), &["e4", "c5"]));
+    }
+
+    #[test]
+    fn repetition_after_undo() {
+        let knight_dance = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        let mut game = play_san_moves(GameState::default(), &knight_dance.repeat(2));
+        assert_eq!(game.status(), GameStatus::ThreefoldRepetition);
+        game.undo_move();
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        game.go_to_ply(4);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        for _ in 0..4 {
+            game.redo_move();
+        }
+        assert_eq!(game.status(), GameStatus::ThreefoldRepetition);
+    }
+
+    #[test]
+    fn deserialize_checks_ply() {
+        let mut game = play_san_moves(GameState::default(), &["e4", "e5", "Nf3"]);
+        game.undo_move();
+        let json = serde_json::to_string(&game).unwrap();
+        assert_eq!(serde_json::from_str::<Game>(&json).unwrap(), game);
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["ply"] = 4.into();
+        assert!(serde_json::from_value::<Game>(value.clone()).is_err());
+        value["ply"] = 3.into();
+        value["states"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<Game>(value).is_err());
+    }
+
+    #[test]
+    fn checkmate() {
+        let game = play_san_moves(GameState::default(), &["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(game.status(), GameStatus::Checkmate(Color::Black));
+    }
+
+    #[test]
+    fn stalemate() {
+        let game = Game::new(get_game_state_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(game.status(), GameStatus::Stalemate);
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_move_rules() {
+        let start = get_game_state_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+        let status = play_san_moves(start.clone(), &["Ra2"]).status();
+        assert_eq!(status, GameStatus::FiftyMoveRule);
+        assert!(status.is_claimable_draw());
+        assert!(!status.is_game_over());
+        assert_eq!(play_san_moves(start, &["e4"]).status(), GameStatus::Ongoing);
+        let start = get_game_state_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 149 105").unwrap();
+        let status = play_san_moves(start, &["Ra2"]).status();
+        assert_eq!(status, GameStatus::SeventyFiveMoveRule);
+        assert!(!status.is_claimable_draw());
+        assert!(status.is_game_over());
+    }
+
+    #[test]
+    fn checkmate_on_fiftieth_move() {
+        let start = get_game_state_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+        assert_eq!(
+            play_san_moves(start, &["Ra8#"]).status(),
+            GameStatus::Checkmate(Color::White)
+        );
+    }
+
+    #[test]
+    fn repetition() {
+        let knight_dance = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        let game = play_san_moves(GameState::default(), &knight_dance[..]);
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        let game = play_san_moves(GameState::default(), &knight_dance.repeat(2));
+        assert_eq!(game.status(), GameStatus::ThreefoldRepetition);
+        assert!(!game.status().is_game_over());
+        let game = play_san_moves(GameState::default(), &knight_dance.repeat(4));
+        assert_eq!(game.status(), GameStatus::FivefoldRepetition);
+        assert!(game.status().is_game_over());
+    }
+
+    #[test]
+    fn repetition_needs_same_castling_rights() {
+        let start = get_game_state_from_fen("r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1").unwrap();
+        let king_walk = ["Kf1", "Kd8", "Ke1", "Ke8"];
+        let game = play_san_moves(start, &king_walk.repeat(2));
+        assert_eq!(game.status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/B3K1B1 w - - 0 1",
+        ] {
+            let game_state = get_game_state_from_fen(fen).unwrap();
+            assert!(game_state.is_insufficient_material(), "{}", fen);
+            assert_eq!(
+                Game::new(game_state).status(),
+                GameStatus::InsufficientMaterial
+            );
+        }
+        for fen in [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KNN1 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        ] {
+            let game_state = get_game_state_from_fen(fen).unwrap();
+            assert!(!game_state.is_insufficient_material(), "{}", fen);
+        }
+    }
+}
@@ -1,6 +1,12 @@
+pub mod chess960;
 pub mod constants;
+pub mod epd;
 pub mod fen_util;
+pub mod game;
+pub mod pgn;
+pub mod san;
 pub mod types;
+pub mod validation;
 
 use std::collections::HashMap;
 
@@ -8,15 +14,32 @@ use constants::*;
 use serde::{Deserialize, Serialize};
 use types::*;
 
-const GLOBAL_WITH_INFO: bool = true;
-
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct GameState {
     pub board: Board,
     pub turn: Color,
     pub castle: CastleAvailability,
     pub en_passant_index: Option<usize>,
-    pub halfmove_counter: u8,
+    pub halfmove_counter: u16,
+    // Starts at 1 and is incremented after each Black move
+    pub fullmove_number: u32,
+    // Zobrist hash of the position, kept up to date by make_move and unmake_move
+    #[serde(serialize_with = "to_str", deserialize_with = "from_str")]
+    pub hash: u64,
+    // Chess960 castling moves are written as the king taking its own rook in UCI notation, see
+    // move_to_uci. Castling itself works the same either way.
+    #[serde(default)]
+    pub chess960: bool,
+}
+
+// Everything make_move overwrites that can not be worked out from the move itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct UndoInfo {
+    captured_piece: Piece,
+    castle: CastleAvailability,
+    en_passant_index: Option<usize>,
+    halfmove_counter: u16,
+    hash: u64,
 }
 
 #[derive(Debug, Default)]
@@ -29,15 +52,49 @@ pub struct GameStateInfo {
     pub in_double_check: bool,
 }
 
+// Which moves a generator produces. Promotions count as captures, since they change the material
+// on the board the same way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MoveKind {
+    All,
+    Captures,
+    Quiet,
+}
+
+impl MoveKind {
+    fn includes(&self, next_move: &Move) -> bool {
+        let is_capture = next_move.capture || next_move.promotion_piece.is_some();
+        match self {
+            MoveKind::All => true,
+            MoveKind::Captures => is_capture,
+            MoveKind::Quiet => !is_capture,
+        }
+    }
+
+    // The squares that pieces other than pawns may move to
+    fn get_target_bitmask(&self, board: &Board, color: Color) -> u64 {
+        match self {
+            MoveKind::All => !board.get_color_bitmask(color),
+            MoveKind::Captures => board.get_color_bitmask(color.opposite()),
+            MoveKind::Quiet => board.get_color_bitmask(Color::Empty),
+        }
+    }
+}
+
 impl Default for GameState {
     fn default() -> Self {
-        GameState {
+        let mut game_state = GameState {
             board: Default::default(),
             turn: Color::White,
             castle: Default::default(),
             en_passant_index: None,
             halfmove_counter: 0,
-        }
+            fullmove_number: 1,
+            hash: 0,
+            chess960: false,
+        };
+        game_state.hash = game_state.compute_hash();
+        game_state
     }
 }
 
@@ -57,72 +114,185 @@ impl GameState {
 
     pub fn perform_move(&self, next_move: Move) -> GameState {
         let mut game_state_clone = self.clone();
+        game_state_clone.make_move(next_move);
+        game_state_clone
+    }
+
+    // Performs the move in place and returns what unmake_move needs to take it back
+    pub fn make_move(&mut self, next_move: Move) -> UndoInfo {
         let Move { from, to, .. } = next_move;
+        let color = self.turn;
+        let (_, moved_piece) = self.board.get_square(from);
+        // In Chess960 the king can castle onto its own rook's square, which is not a capture
+        let (captured_color, captured_piece) = if next_move.castle {
+            (Color::Empty, Piece::Empty)
+        } else {
+            self.board.get_square(to)
+        };
+        let undo_info = UndoInfo {
+            captured_piece,
+            castle: self.castle.clone(),
+            en_passant_index: self.en_passant_index,
+            halfmove_counter: self.halfmove_counter,
+            hash: self.hash,
+        };
+
+        let mut hash = self.hash ^ ZOBRIST_BLACK_TO_MOVE_KEY ^ self.get_zobrist_en_passant_hash();
+        hash ^= get_zobrist_piece_key(color, moved_piece, from)
+            ^ get_zobrist_piece_key(color, moved_piece, to);
+        if captured_color != Color::Empty {
+            hash ^= get_zobrist_piece_key(captured_color, captured_piece, to);
+        }
 
-        game_state_clone.board.move_from_to(from, to);
         if next_move.castle {
-            // Move the rook
-            if to == 2 {
-                game_state_clone.board.move_from_to(0, 3);
-            } else if to == 6 {
-                game_state_clone.board.move_from_to(7, 5);
-            } else if to == 58 {
-                game_state_clone.board.move_from_to(56, 59);
-            } else if to == 62 {
-                game_state_clone.board.move_from_to(63, 61);
-            }
+            let (rook_from, rook_to) = get_castle_rook_move(&self.castle, to);
+            self.board.clear_square(from);
+            self.board.clear_square(rook_from);
+            self.board.update_square(to, color, Piece::King);
+            self.board.update_square(rook_to, color, Piece::Rook);
+            hash ^= get_zobrist_piece_key(color, Piece::Rook, rook_from)
+                ^ get_zobrist_piece_key(color, Piece::Rook, rook_to);
+        } else {
+            self.board.move_from_to(from, to);
         }
 
-        game_state_clone.update_castle_availability(from, to);
+        hash ^= get_zobrist_castle_hash(&self.castle);
+        self.update_castle_availability(color, moved_piece, from, to);
+        hash ^= get_zobrist_castle_hash(&self.castle);
 
         if next_move.en_passant {
-            let captured_pawn_index = if from > to { to + 8 } else { to - 8 };
-            game_state_clone.board.clear_square(captured_pawn_index);
+            let captured_pawn_index = get_en_passant_captured_index(from, to);
+            self.board.clear_square(captured_pawn_index);
+            hash ^= get_zobrist_piece_key(color.opposite(), Piece::Pawn, captured_pawn_index);
         }
 
         if next_move.two_square_pawn_move {
-            game_state_clone.en_passant_index = Some((from + to) / 2);
+            self.en_passant_index = Some((from + to) / 2);
         } else {
-            game_state_clone.en_passant_index = None;
+            self.en_passant_index = None;
         }
 
         // If the move is a capture or a pawn move, reset the halfmove counter. Otherwise, increment it
-        if next_move.capture || game_state_clone.board.get_square(from).1 == Piece::Pawn {
-            game_state_clone.halfmove_counter = 0;
+        if next_move.capture || moved_piece == Piece::Pawn {
+            self.halfmove_counter = 0;
         } else {
-            game_state_clone.halfmove_counter += 1;
+            self.halfmove_counter = self.halfmove_counter.saturating_add(1);
         }
 
         if let Some(promotion_piece) = next_move.promotion_piece {
-            game_state_clone
-                .board
-                .update_square(to, game_state_clone.turn, promotion_piece);
+            self.board.update_square(to, color, promotion_piece);
+            hash ^= get_zobrist_piece_key(color, Piece::Pawn, to)
+                ^ get_zobrist_piece_key(color, promotion_piece, to);
         }
 
-        game_state_clone.turn = game_state_clone.turn.opposite();
+        if color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.turn = color.opposite();
+
+        // The en passant key depends on the side to move, so it is removed before the move and
+        // added back after
+        hash ^= self.get_zobrist_en_passant_hash();
+        self.hash = hash;
+        debug_assert_eq!(
+            self.hash,
+            self.compute_hash(),
+            "incremental hash does not match computed hash after {:?}",
+            next_move
+        );
 
-        game_state_clone
+        undo_info
     }
 
-    fn update_castle_availability(&mut self, from: usize, to: usize) {
-        let black_king_moved = from == 4;
-        let black_queen_rook_moved_or_captured = from == 0 || to == 0;
-        let black_king_rook_moved_or_captured = from == 7 || to == 7;
-        let white_king_moved = from == 60;
-        let white_queen_rook_moved_or_captured = from == 56 || to == 56;
-        let white_king_rook_moved_or_captured = from == 63 || to == 63;
+    // Takes back a move made with make_move, which must have been the last move made
+    pub fn unmake_move(&mut self, next_move: Move, undo_info: UndoInfo) {
+        let Move { from, to, .. } = next_move;
+        let color = self.turn.opposite();
+        self.turn = color;
+        if color == Color::Black {
+            self.fullmove_number -= 1;
+        }
 
-        if black_king_moved || black_king_rook_moved_or_captured {
-            self.castle.black_kingside = false;
+        if next_move.castle {
+            let (rook_from, rook_to) = get_castle_rook_move(&undo_info.castle, to);
+            self.board.clear_square(to);
+            self.board.clear_square(rook_to);
+            self.board.update_square(from, color, Piece::King);
+            self.board.update_square(rook_from, color, Piece::Rook);
+        } else {
+            if next_move.promotion_piece.is_some() {
+                self.board.update_square(to, color, Piece::Pawn);
+            }
+            self.board.move_from_to(to, from);
+            if undo_info.captured_piece != Piece::Empty {
+                self.board
+                    .update_square(to, color.opposite(), undo_info.captured_piece);
+            }
+            if next_move.en_passant {
+                let captured_pawn_index = get_en_passant_captured_index(from, to);
+                self.board
+                    .update_square(captured_pawn_index, color.opposite(), Piece::Pawn);
+            }
         }
-        if black_king_moved || black_queen_rook_moved_or_captured {
-            self.castle.black_queenside = false;
+
+        self.castle = undo_info.castle;
+        self.en_passant_index = undo_info.en_passant_index;
+        self.halfmove_counter = undo_info.halfmove_counter;
+        self.hash = undo_info.hash;
+    }
+
+    // Computes the Zobrist hash of the position from scratch
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for index in 0..64 {
+            let (color, piece) = self.board.get_square(index);
+            if color != Color::Empty {
+                hash ^= get_zobrist_piece_key(color, piece, index);
+            }
         }
-        if white_king_moved || white_king_rook_moved_or_captured {
-            self.castle.white_kingside = false;
+        if self.turn == Color::Black {
+            hash ^= ZOBRIST_BLACK_TO_MOVE_KEY;
         }
-        if white_king_moved || white_queen_rook_moved_or_captured {
-            self.castle.white_queenside = false;
+        hash ^ get_zobrist_castle_hash(&self.castle) ^ self.get_zobrist_en_passant_hash()
+    }
+
+    // The en passant square is only hashed if a pawn of the side to move can capture onto it, so
+    // that positions which only differ by an unusable en passant square hash the same
+    fn get_zobrist_en_passant_hash(&self) -> u64 {
+        let Some(en_passant_index) = self.en_passant_index else {
+            return 0;
+        };
+        let pawn_bitmask =
+            self.board.get_color_bitmask(self.turn) & self.board.get_piece_bitmask(Piece::Pawn);
+        let can_capture = get_pawn_mailbox_attack_indices(&self.turn.opposite(), en_passant_index)
+            .iter()
+            .filter_map(|&mailbox_index| MAILBOX[mailbox_index])
+            .any(|pawn_index| pawn_bitmask & (1 << pawn_index) != 0);
+        if can_capture {
+            ZOBRIST_EN_PASSANT_KEYS[en_passant_index % 8]
+        } else {
+            0
+        }
+    }
+
+    // Moving the king loses both rights, and moving a rook or having it captured loses the right
+    // that uses it
+    fn update_castle_availability(
+        &mut self,
+        color: Color,
+        moved_piece: Piece,
+        from: usize,
+        to: usize,
+    ) {
+        for right_color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                let king_moved = moved_piece == Piece::King && right_color == color;
+                let rook_index = self.castle.get_rook_index(right_color, kingside);
+                let rook_moved_or_captured = rook_index == Some(from) || rook_index == Some(to);
+                if king_moved || rook_moved_or_captured {
+                    *self.castle.get_mut(right_color, kingside) = None;
+                }
+            }
         }
     }
 
@@ -134,45 +304,26 @@ impl GameState {
     }
 
     pub fn generate_attack_mask(&self, game_state_info: &mut GameStateInfo) {
-        let opponent_bitmask = self.board.get_color_bitmask(self.turn.opposite());
+        let opponent_color = self.turn.opposite();
+        let opponent_bitmask = self.board.get_color_bitmask(opponent_color);
+        // Sliders attack through the king, so that it can not step back along the line of a check
+        let king_bitmask =
+            self.board.get_piece_bitmask(Piece::King) & self.board.get_color_bitmask(self.turn);
+        let occupied = self.board.get_occupied_bitmask() & !king_bitmask;
         (0..64).for_each(|index| {
             if (1 << index) & opponent_bitmask > 0 {
-                let mailbox_start_index = BOARD_INDEX_TO_MAILBOX_INDEX[index];
                 let (_color, piece) = self.board.get_square(index);
-                if piece == Piece::Pawn {
-                    let pawn_mailbox_offsets =
-                        get_pawn_mailbox_attack_indices(&self.turn.opposite(), index);
-                    for target_mailbox_index in pawn_mailbox_offsets {
-                        if let Some(target_index) = MAILBOX[target_mailbox_index] {
-                            game_state_info.attack_mask |= 1 << target_index;
-                        }
-                    }
-                }
-                let mailbox_direction_offsets = get_piece_mailbox_direction_offsets(&piece);
-                for mailbox_offset in mailbox_direction_offsets {
-                    let target_mailbox_index_plus = mailbox_start_index + mailbox_offset;
-                    let target_mailbox_index_minus = mailbox_start_index - mailbox_offset;
-                    for mut target_mailbox_index in
-                        [target_mailbox_index_plus, target_mailbox_index_minus]
-                    {
-                        while let Some(target_index) = MAILBOX[target_mailbox_index] {
-                            game_state_info.attack_mask |= 1 << target_index;
-                            let (attacked_color, attacked_piece) = self.board.get_square(target_index);
-                            if !piece.is_slide()
-                                || attacked_piece != Piece::Empty
-                                    // Ignore king for calculating attack mak
-                                    && !(attacked_color == self.turn && attacked_piece == Piece::King)
-                            {
-                                break;
-                            }
-                            if target_mailbox_index < mailbox_start_index {
-                                target_mailbox_index -= mailbox_offset;
-                            } else {
-                                target_mailbox_index += mailbox_offset;
-                            }
-                        }
-                    }
-                }
+                game_state_info.attack_mask |= match piece {
+                    Piece::Pawn => get_pawn_mailbox_attack_indices(&opponent_color, index)
+                        .iter()
+                        .filter_map(|&target_mailbox_index| MAILBOX[target_mailbox_index])
+                        .fold(0, |attack_mask, target_index| {
+                            attack_mask | 1 << target_index
+                        }),
+                    Piece::Knight => KNIGHT_ATTACK_BITMASKS[index],
+                    Piece::King => KING_ATTACK_BITMASKS[index],
+                    _ => get_slide_piece_attack_bitmask(&piece, index, occupied),
+                };
             }
         });
     }
@@ -180,50 +331,47 @@ impl GameState {
     pub fn generate_pin_masks(&self, game_state_info: &mut GameStateInfo) {
         let king_index = self.board.find_king(self.turn);
         let mailbox_start_index = BOARD_INDEX_TO_MAILBOX_INDEX[king_index];
+        let own_bitmask = self.board.get_color_bitmask(self.turn);
+        let opponent_bitmask = self.board.get_color_bitmask(self.turn.opposite());
+        let queens = self.board.get_piece_bitmask(Piece::Queen);
         // Check Bishops, Rooks, and Queens
         for piece_to_check in [Piece::Rook, Piece::Bishop] {
-            let mailbox_direction_offsets = get_piece_mailbox_direction_offsets(&piece_to_check);
-            for mailbox_offset in mailbox_direction_offsets {
-                let target_mailbox_index_plus = mailbox_start_index + mailbox_offset;
-                let target_mailbox_index_minus = mailbox_start_index - mailbox_offset;
-                for mut target_mailbox_index in
-                    [target_mailbox_index_plus, target_mailbox_index_minus]
-                {
-                    let mut found_piece_index = None;
-                    let mut mask: u64 = 0;
-                    while let Some(target_index) = MAILBOX[target_mailbox_index] {
-                        mask |= 1 << target_index;
-                        let (color, piece) = self.board.get_square(target_index);
-                        if color == self.turn {
-                            if found_piece_index.is_some() {
-                                // Found second piece, no pin possible
-                                break;
-                            } else {
-                                found_piece_index = Some(target_index);
-                            }
-                        } else if color == self.turn.opposite() {
-                            if piece == Piece::Queen || piece == piece_to_check {
-                                if let Some(found_piece_index) = found_piece_index {
-                                    game_state_info.pins.insert(found_piece_index, mask);
-                                } else {
-                                    game_state_info.in_double_check = game_state_info.in_check;
-                                    game_state_info.in_check = true;
-                                    if game_state_info.in_double_check {
-                                        // Double check means only king moves are valid; no need to check anything more
-                                        return;
-                                    } else {
-                                        game_state_info.check_mask = mask;
-                                    }
-                                }
-                            }
-                            break;
-                        }
-                        if target_mailbox_index < mailbox_start_index {
-                            target_mailbox_index -= mailbox_offset;
+            let sliders =
+                (self.board.get_piece_bitmask(piece_to_check) | queens) & opponent_bitmask;
+            // Looking through our own pieces finds the nearest opponent piece on each line from the
+            // king, which is either checking the king, pinning a piece or blocked
+            let mut candidates =
+                get_slide_piece_attack_bitmask(&piece_to_check, king_index, opponent_bitmask)
+                    & sliders;
+            while candidates != 0 {
+                let slider_index = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                let between_mask =
+                    get_slide_piece_attack_bitmask(&piece_to_check, king_index, 1 << slider_index)
+                        & get_slide_piece_attack_bitmask(
+                            &piece_to_check,
+                            slider_index,
+                            1 << king_index,
+                        );
+                let mask = between_mask | 1 << slider_index;
+                let blockers = between_mask & own_bitmask;
+                match blockers.count_ones() {
+                    0 => {
+                        game_state_info.in_double_check = game_state_info.in_check;
+                        game_state_info.in_check = true;
+                        if game_state_info.in_double_check {
+                            // Double check means only king moves are valid; no need to check anything more
+                            return;
                         } else {
-                            target_mailbox_index += mailbox_offset;
+                            game_state_info.check_mask = mask;
                         }
                     }
+                    1 => {
+                        let pinned_index = blockers.trailing_zeros() as usize;
+                        game_state_info.pins.insert(pinned_index, mask);
+                    }
+                    // Two or more pieces in the way, no pin possible
+                    _ => (),
                 }
             }
         }
@@ -255,19 +403,23 @@ impl GameState {
             if let Some(target_index) = MAILBOX[target_mailbox_index] {
                 let (color, piece) = self.board.get_square(target_index);
                 if color == self.turn.opposite() && piece == Piece::Pawn {
+                    // A pawn can give check at the same time as a discovered slider check
+                    game_state_info.in_double_check = game_state_info.in_check;
                     game_state_info.in_check = true;
-                    game_state_info.check_mask = 1 << target_index;
-                    // Can't double check with pawn, no need to check more
+                    if !game_state_info.in_double_check {
+                        game_state_info.check_mask = 1 << target_index;
+                    }
+                    // Two pawns can't check the king at once, no need to check more
                     break;
                 }
             }
         }
     }
 
-    // Generates pseudo legal moves, then removes the ones with the king in check.
-    // This is slow and should be updated later.
+    // The position after each legal move. Prefer generate_legal_moves with make_move and
+    // unmake_move where the positions are not all needed at once.
     pub fn generate_legal_states(&self) -> Vec<GameState> {
-        self.generate_legal_states_inner(GLOBAL_WITH_INFO)
+        self.generate_legal_states_inner(true)
     }
 
     pub fn generate_legal_states_inner(&self, with_info: bool) -> Vec<GameState> {
@@ -286,9 +438,13 @@ impl GameState {
                 let game_state_clone = self.perform_move(next_move);
                 if next_move.castle {
                     let castle_into_check = game_state_clone.is_opponent_in_check();
-                    let check_index = (next_move.from + next_move.to) / 2;
-                    let castle_through_check =
-                        game_state_clone.board.is_index_under_attack(check_index);
+                    let (low, high) = (
+                        next_move.from.min(next_move.to),
+                        next_move.from.max(next_move.to),
+                    );
+                    let castle_through_check = (low..=high)
+                        .filter(|&index| index != next_move.to)
+                        .any(|index| game_state_clone.board.is_index_under_attack(index));
                     if castle_into_check || castle_through_check {
                         None
                     } else {
@@ -303,50 +459,157 @@ impl GameState {
             .collect()
     }
 
-    #[allow(dead_code)]
-    pub fn generate_legal_moves_at_depth(&self, depth: usize) -> Vec<GameState> {
-        self.generate_legal_moves_at_depth_inner(depth, true)
+    // Counts the leaf nodes of the legal move tree to the given depth, for checking move generation
+    // against known results
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|next_move| {
+                let undo_info = self.make_move(next_move);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(next_move, undo_info);
+                nodes
+            })
+            .sum()
+    }
+
+    // The perft count below each legal move, keyed by the move in UCI notation and sorted by it, so
+    // that a wrong total can be narrowed down by diffing against another engine
+    pub fn perft_divide(&mut self, depth: usize) -> Vec<(String, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+        let mut divide: Vec<(String, u64)> = self
+            .generate_legal_moves()
+            .into_iter()
+            .map(|next_move| {
+                let uci = self.move_to_uci(next_move);
+                let undo_info = self.make_move(next_move);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(next_move, undo_info);
+                (uci, nodes)
+            })
+            .collect();
+        divide.sort();
+        divide
     }
 
-    pub fn generate_legal_moves_at_depth_inner(
-        &self,
-        depth: usize,
-        with_info: bool,
-    ) -> Vec<GameState> {
-        let mut game_states = self.generate_legal_states_inner(with_info);
+    // All legal moves for the side to move
+    pub fn generate_legal_moves(&self) -> Vec<Move> {
+        self.generate_legal_moves_of_kind(MoveKind::All)
+    }
 
-        let mut curr_depth = 1;
-        while curr_depth < depth {
-            curr_depth += 1;
-            game_states = game_states.iter_mut().fold(vec![], |mut next, game_state| {
-                let mut next_game_state = game_state.generate_legal_states_inner(with_info);
-                next.append(&mut next_game_state);
-                next
-            });
-        }
+    // Legal captures, including en passant, and promotions: the moves that change the material on
+    // the board, which quiescence search looks at
+    pub fn generate_legal_captures(&self) -> Vec<Move> {
+        self.generate_legal_moves_of_kind(MoveKind::Captures)
+    }
+
+    // Every legal move that generate_legal_captures leaves out, including castling
+    pub fn generate_legal_quiet_moves(&self) -> Vec<Move> {
+        self.generate_legal_moves_of_kind(MoveKind::Quiet)
+    }
 
-        game_states
+    // For moves from outside the engine, such as the web UI or a protocol. The flags have to match
+    // the generated move as well as the squares, see parse_uci_move for resolving those.
+    pub fn is_legal(&self, next_move: Move) -> bool {
+        self.generate_legal_moves().contains(&next_move)
     }
 
-    pub fn generate_pseudo_legal_moves(&self, with_info: bool) -> Vec<Move> {
+    fn generate_legal_moves_of_kind(&self, kind: MoveKind) -> Vec<Move> {
+        let game_state_info = self.generate_game_state_info();
+        self.generate_pseudo_legal_moves_inner(self.turn, &game_state_info, kind)
+    }
+
+    // Without the attack and pin information, the moves may leave the king in check and have to be
+    // filtered by playing them out, as generate_legal_states_inner does. With it, they are legal
+    // and the same as generate_legal_moves.
+    pub(crate) fn generate_pseudo_legal_moves(&self, with_info: bool) -> Vec<Move> {
         let game_state_info = if with_info {
             self.generate_game_state_info()
         } else {
             GameStateInfo::default()
         };
-        self.generate_pseudo_legal_moves_inner(self.turn, &game_state_info)
+        self.generate_pseudo_legal_moves_inner(self.turn, &game_state_info, MoveKind::All)
+    }
+
+    // Like Move::to_uci, except that Chess960 castling is written as the king taking its own rook,
+    // e.g. f1h1, since the king may not move at all or may land where a plain king move would
+    pub fn move_to_uci(&self, next_move: Move) -> String {
+        let to = self.get_uci_to_index(next_move);
+        if to == next_move.to {
+            next_move.to_uci()
+        } else {
+            fen_util::get_square_from_index(next_move.from) + &fen_util::get_square_from_index(to)
+        }
+    }
+
+    fn get_uci_to_index(&self, next_move: Move) -> usize {
+        if self.chess960 && next_move.castle {
+            get_castle_rook_move(&self.castle, next_move.to).0
+        } else {
+            next_move.to
+        }
+    }
+
+    // Resolves long algebraic notation against the legal moves, so that flags like castle and
+    // en passant are set the same way as for generated moves. Chess960 castling must be given as
+    // the king taking its own rook, see move_to_uci.
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, MoveParseError> {
+        let invalid_format = || MoveParseError::InvalidFormat(text.to_string());
+        let chars: Vec<char> = text.chars().collect();
+        let (from, to, promotion) = match chars.as_slice() {
+            [from_file, from_rank, to_file, to_rank, promotion @ ..] if promotion.len() <= 1 => (
+                parse_square(*from_file, *from_rank).ok_or_else(invalid_format)?,
+                parse_square(*to_file, *to_rank).ok_or_else(invalid_format)?,
+                promotion.first(),
+            ),
+            _ => return Err(invalid_format()),
+        };
+        let promotion_piece = match promotion {
+            None => None,
+            Some('n') => Some(Piece::Knight),
+            Some('b') => Some(Piece::Bishop),
+            Some('r') => Some(Piece::Rook),
+            Some('q') => Some(Piece::Queen),
+            Some(_) => return Err(invalid_format()),
+        };
+        self.generate_legal_moves()
+            .into_iter()
+            .find(|&m| {
+                m.from == from
+                    && self.get_uci_to_index(m) == to
+                    && m.promotion_piece == promotion_piece
+            })
+            .ok_or_else(|| MoveParseError::IllegalMove(text.to_string()))
     }
 
     fn generate_pseudo_legal_moves_inner(
         &self,
         color: Color,
         game_state_info: &GameStateInfo,
+        kind: MoveKind,
     ) -> Vec<Move> {
         let board = &self.board;
+        let target_bitmask = kind.get_target_bitmask(board, color);
         // In double check, only calculate king moves
         if game_state_info.in_double_check {
             let king_index = board.find_king(color);
-            return self.gen_moves_piece(color, &Piece::King, king_index, vec![], game_state_info);
+            return self.gen_moves_piece(
+                color,
+                &Piece::King,
+                king_index,
+                target_bitmask,
+                vec![],
+                game_state_info,
+            );
         }
         let mut moves = vec![];
         let mask_to_check = board.get_color_bitmask(color);
@@ -356,14 +619,29 @@ impl GameState {
                 moves = match square_piece {
                     Piece::Pawn => self.gen_moves_pawn(color, index, moves, game_state_info),
                     Piece::Empty => moves,
-                    _ => self.gen_moves_piece(color, &square_piece, index, moves, game_state_info),
+                    _ => self.gen_moves_piece(
+                        color,
+                        &square_piece,
+                        index,
+                        target_bitmask,
+                        moves,
+                        game_state_info,
+                    ),
                 };
                 // Can't castle out of check
-                if square_piece == Piece::King && !game_state_info.in_check {
+                if square_piece == Piece::King
+                    && !game_state_info.in_check
+                    && kind != MoveKind::Captures
+                {
                     moves = self.gen_castle_moves(color, moves, game_state_info);
                 }
             }
         }
+        // Pieces other than pawns only generate moves to the target squares, but pawn captures and
+        // pushes depend on more than the target square
+        if kind != MoveKind::All {
+            moves.retain(|next_move| kind.includes(next_move));
+        }
 
         moves
     }
@@ -413,33 +691,32 @@ impl GameState {
         let en_passant_index = self.en_passant_index.unwrap_or(100);
         for mailbox_attack_index in mailbox_attack_indices {
             if let Some(attack_index) = MAILBOX[mailbox_attack_index] {
-                if self.is_move_legal(index, attack_index, &Piece::Pawn, game_state_info) {
-                    if attack_index == en_passant_index {
-                        // Maybe optimizable, but rare enough that it's not worth it
-                        // (at most 2 possible en_passant moves)
-                        let game_state_clone =
-                            self.perform_move(Move::en_passant(index, attack_index));
-                        if !game_state_clone.is_opponent_in_check() {
-                            moves.push(Move::en_passant(index, attack_index));
-                        }
-                    } else {
-                        let is_target_empty = &self.board.is_index_empty(attack_index);
-                        if !is_target_empty && !self.board.is_index_of_color(attack_index, color) {
-                            if color == Color::White && attack_index < 8
-                                || color == Color::Black && attack_index > 55
-                            {
-                                [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
-                                    .iter()
-                                    .for_each(|piece| {
-                                        moves.push(Move::promotion_capture(
-                                            index,
-                                            attack_index,
-                                            *piece,
-                                        ));
-                                    });
-                            } else {
-                                moves.push(Move::capture(index, attack_index));
-                            }
+                if attack_index == en_passant_index {
+                    // The check mask does not cover en passant, which can take a checking pawn
+                    // without landing on its square, so the move is made and checked instead.
+                    // Maybe optimizable, but rare enough that it's not worth it
+                    // (at most 2 possible en_passant moves)
+                    let game_state_clone = self.perform_move(Move::en_passant(index, attack_index));
+                    if !game_state_clone.is_opponent_in_check() {
+                        moves.push(Move::en_passant(index, attack_index));
+                    }
+                } else if self.is_move_legal(index, attack_index, &Piece::Pawn, game_state_info) {
+                    let is_target_empty = &self.board.is_index_empty(attack_index);
+                    if !is_target_empty && !self.board.is_index_of_color(attack_index, color) {
+                        if color == Color::White && attack_index < 8
+                            || color == Color::Black && attack_index > 55
+                        {
+                            [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+                                .iter()
+                                .for_each(|piece| {
+                                    moves.push(Move::promotion_capture(
+                                        index,
+                                        attack_index,
+                                        *piece,
+                                    ));
+                                });
+                        } else {
+                            moves.push(Move::capture(index, attack_index));
                         }
                     }
                 }
@@ -454,126 +731,62 @@ impl GameState {
         color: Color,
         piece: &Piece,
         index: usize,
+        target_bitmask: u64,
         mut moves: Vec<Move>,
         game_state_info: &GameStateInfo,
     ) -> Vec<Move> {
-        let mailbox_index = BOARD_INDEX_TO_MAILBOX_INDEX[index];
-        let mailbox_offsets = get_piece_mailbox_direction_offsets(piece);
-        for mailbox_offset in mailbox_offsets {
-            if piece.is_slide() {
-                moves = self.gen_moves_slide_direction(
-                    color,
-                    piece,
-                    index,
-                    mailbox_index,
-                    *mailbox_offset,
-                    moves,
-                    game_state_info,
-                );
-            } else {
-                moves = self.gen_moves_hop_direction(
-                    color,
-                    piece,
-                    index,
-                    mailbox_index,
-                    *mailbox_offset,
-                    moves,
-                    game_state_info,
-                );
+        let attack_bitmask = match piece {
+            Piece::Knight => KNIGHT_ATTACK_BITMASKS[index],
+            Piece::King => KING_ATTACK_BITMASKS[index],
+            _ => get_slide_piece_attack_bitmask(piece, index, self.board.get_occupied_bitmask()),
+        };
+        let mut targets = attack_bitmask & target_bitmask;
+        while targets != 0 {
+            let target_index = targets.trailing_zeros() as usize;
+            targets &= targets - 1;
+            if self.is_move_legal(index, target_index, piece, game_state_info) {
+                moves.extend(self.gen_move_to_index(color, index, target_index));
             }
         }
         moves
     }
 
-    fn gen_moves_hop_direction(
+    fn gen_castle_moves(
         &self,
         color: Color,
-        piece: &Piece,
-        index: usize,
-        mailbox_index: usize,
-        mailbox_offset: usize,
         mut moves: Vec<Move>,
         game_state_info: &GameStateInfo,
     ) -> Vec<Move> {
-        let target_mailbox_index_plus = mailbox_index + mailbox_offset;
-        let target_mailbox_index_minus = mailbox_index - mailbox_offset;
-        for target_mailbox_index in [target_mailbox_index_plus, target_mailbox_index_minus] {
-            let generated_move = self.gen_move_from_mailbox(color, target_mailbox_index, index);
-            match generated_move {
-                // Off the board
-                None => (),
-                Some(generated_move) => {
-                    if self.is_move_legal(index, generated_move.to, piece, game_state_info) {
-                        moves.push(generated_move);
-                    }
-                }
-            };
+        if game_state_info.in_check {
+            return moves;
         }
-        moves
-    }
-
-    fn gen_moves_slide_direction(
-        &self,
-        color: Color,
-        piece: &Piece,
-        index: usize,
-        mailbox_index: usize,
-        mailbox_offset: usize,
-        mut moves: Vec<Move>,
-        game_state_info: &GameStateInfo,
-    ) -> Vec<Move> {
-        let target_mailbox_index_plus = mailbox_index + mailbox_offset;
-        let target_mailbox_index_minus = mailbox_index - mailbox_offset;
-        for mut target_mailbox_index in [target_mailbox_index_plus, target_mailbox_index_minus] {
-            while let Some(generated_move) =
-                self.gen_move_from_mailbox(color, target_mailbox_index, index)
+        let back_rank_start = get_back_rank_start(color);
+        let king_index = self.board.find_king(color);
+        if !(back_rank_start..back_rank_start + 8).contains(&king_index) {
+            return moves;
+        }
+        // Queenside first, to keep the order the moves have always been generated in
+        for kingside in [false, true] {
+            let Some(rook_index) = self.castle.get_rook_index(color, kingside) else {
+                continue;
+            };
+            if self.board.get_square(rook_index) != (color, Piece::Rook) {
+                continue;
+            }
+            let (king_to, rook_to) = get_castle_destinations(color, kingside);
+            if !self
+                .board
+                .is_castle_open(king_index, rook_index, king_to, rook_to, game_state_info)
             {
-                let capture = generated_move.capture;
-                if self.is_move_legal(index, generated_move.to, piece, game_state_info) {
-                    moves.push(generated_move);
-                }
-                if capture {
-                    break;
-                }
-                if target_mailbox_index < mailbox_index {
-                    target_mailbox_index -= mailbox_offset;
-                } else {
-                    target_mailbox_index += mailbox_offset;
-                }
+                continue;
             }
-        }
-        moves
-    }
-
-    fn gen_castle_moves(
-        &self,
-        color: Color,
-        mut moves: Vec<Move>,
-        game_state_info: &GameStateInfo,
-    ) -> Vec<Move> {
-        if !game_state_info.in_check {
-            if color == Color::Black {
-                if self.castle.black_queenside
-                    && self.board.castle_black_queenside_open(game_state_info)
-                {
-                    moves.push(Move::castle(4, 2));
-                }
-                if self.castle.black_kingside
-                    && self.board.castle_black_kingside_open(game_state_info)
-                {
-                    moves.push(Move::castle(4, 6));
-                }
-            } else {
-                if self.castle.white_queenside
-                    && self.board.castle_white_queenside_open(game_state_info)
-                {
-                    moves.push(Move::castle(60, 58));
-                }
-                if self.castle.white_kingside
-                    && self.board.castle_white_kingside_open(game_state_info)
-                {
-                    moves.push(Move::castle(60, 62));
-                }
+            let castle_move = Move::castle(king_index, king_to);
+            // The attack mask counts the castling rook as a blocker. A rook that does not start in
+            // the corner, as in Chess960, can be all that shields the king's destination from a
+            // rook or queen further along the back rank.
+            let is_rook_in_corner = rook_index % 8 == 0 || rook_index % 8 == 7;
+            if is_rook_in_corner || !self.perform_move(castle_move).is_opponent_in_check() {
+                moves.push(castle_move);
             }
         }
         moves
@@ -592,34 +805,18 @@ impl GameState {
             false
         } else if game_state_info.in_check {
             // Already checked above that the king is not moving into check
-            return piece == &Piece::King
+            piece == &Piece::King
             // Pinned pieces cannot block check
                 || !game_state_info.pins.contains_key(&from_index)
-                    && game_state_info.check_mask & to_mask > 0;
+                    && game_state_info.check_mask & to_mask > 0
         } else if let Some(pin_mask) = game_state_info.pins.get(&from_index) {
             // Pinned knights cannot move
-            return piece != &Piece::Knight && pin_mask & to_mask > 0;
+            piece != &Piece::Knight && pin_mask & to_mask > 0
         } else {
             true
         }
     }
 
-    fn gen_move_from_mailbox(
-        &self,
-        color: Color,
-        target_square_mailbox_index: usize,
-        start_index: usize,
-    ) -> Option<Move> {
-        let target_square_index = MAILBOX[target_square_mailbox_index];
-        match target_square_index {
-            // Off the board
-            None => None,
-            Some(target_square_index) => {
-                self.gen_move_to_index(color, start_index, target_square_index)
-            }
-        }
-    }
-
     fn gen_move_to_index(&self, color: Color, from: usize, to: usize) -> Option<Move> {
         if self.board.is_index_empty(to) {
             Some(Move::new(from, to))
@@ -648,34 +845,277 @@ fn get_pawn_mailbox_attack_indices(turn: &Color, index: usize) -> [usize; 2] {
     }
 }
 
-fn get_piece_mailbox_direction_offsets(piece: &Piece) -> &[usize] {
+// Where the king and rook land when castling, the same in Chess960 as in standard chess
+fn get_castle_destinations(color: Color, kingside: bool) -> (usize, usize) {
+    let back_rank_start = get_back_rank_start(color);
+    if kingside {
+        (back_rank_start + 6, back_rank_start + 5)
+    } else {
+        (back_rank_start + 2, back_rank_start + 3)
+    }
+}
+
+// The rook's from and to squares for a castle move landing the king on `king_to`, using the
+// castling rights from before the move
+fn get_castle_rook_move(castle: &CastleAvailability, king_to: usize) -> (usize, usize) {
+    let (color, kingside) = match king_to {
+        2 => (Color::Black, false),
+        6 => (Color::Black, true),
+        58 => (Color::White, false),
+        62 => (Color::White, true),
+        _ => unreachable!("invalid castle target {}", king_to),
+    };
+    let rook_from = castle
+        .get_rook_index(color, kingside)
+        .unwrap_or_else(|| unreachable!("castle to {} without the right", king_to));
+    (rook_from, get_castle_destinations(color, kingside).1)
+}
+
+// The captured pawn is beside the pawn's starting square, not on the square it moves to
+fn get_en_passant_captured_index(from: usize, to: usize) -> usize {
+    if from > to {
+        to + 8
+    } else {
+        to - 8
+    }
+}
+
+fn parse_square(file: char, rank: char) -> Option<usize> {
+    if ('a'..='h').contains(&file) && ('1'..='8').contains(&rank) {
+        Some(fen_util::get_index_from_square(file, rank))
+    } else {
+        None
+    }
+}
+
+fn get_zobrist_piece_key(color: Color, piece: Piece, index: usize) -> u64 {
+    ZOBRIST_PIECE_KEYS[(color as usize - 1) * 6 + piece as usize - 1][index]
+}
+
+fn get_zobrist_castle_hash(castle: &CastleAvailability) -> u64 {
+    let mut hash = 0;
+    if castle.white_kingside.is_some() {
+        hash ^= ZOBRIST_CASTLE_KEYS[0];
+    }
+    if castle.white_queenside.is_some() {
+        hash ^= ZOBRIST_CASTLE_KEYS[1];
+    }
+    if castle.black_kingside.is_some() {
+        hash ^= ZOBRIST_CASTLE_KEYS[2];
+    }
+    if castle.black_queenside.is_some() {
+        hash ^= ZOBRIST_CASTLE_KEYS[3];
+    }
+    hash
+}
+
+fn get_slide_piece_attack_bitmask(piece: &Piece, index: usize, occupied: u64) -> u64 {
     match piece {
-        Piece::Bishop => &DIAGONAL_MAILBOX_DIRECTION_OFFSETS,
-        Piece::Rook => &CARDINAL_MAILBOX_DIRECTION_OFFSETS,
-        Piece::Queen | Piece::King => &ALL_MAILBOX_DIRECTION_OFFSETS,
-        Piece::Knight => &KNIGHT_MAILBOX_DIRECTION_OFFSETS,
-        // Pawn moves are calculated differently
-        _ => &[],
+        Piece::Bishop => get_bishop_attack_bitmask(index, occupied),
+        Piece::Rook => get_rook_attack_bitmask(index, occupied),
+        Piece::Queen => {
+            get_rook_attack_bitmask(index, occupied) | get_bishop_attack_bitmask(index, occupied)
+        }
+        _ => unreachable!("{:?} is not a sliding piece", piece),
     }
 }
 
 #[cfg(test)]
 mod state_tests {
     use super::fen_util::*;
+    use super::*;
 
     #[test]
     fn in_check_test() {
         let game_state =
-            get_game_state_from_fen("rnbqkbnr/ppp1pppp/3p4/1B6/8/4P3/PPPP1PPP/RNBQK1NR b KQkq -");
+            get_game_state_from_fen("rnbqkbnr/ppp1pppp/3p4/1B6/8/4P3/PPPP1PPP/RNBQK1NR b KQkq -")
+                .unwrap();
         assert!(game_state.is_in_check());
         assert!(!game_state.is_opponent_in_check());
     }
 
+    #[test]
+    fn double_check_with_pawn() {
+        // The queen on e7 and the pawn on f3 both give check, so only king moves are legal
+        let game_state =
+            get_game_state_from_fen("r3k2b/p1ppq3/1n3R2/8/8/5p1P/PPPpKP1P/R7 w q - 0 1").unwrap();
+        let moves = game_state.generate_legal_moves();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|m| m.from == 52));
+        let info = game_state.generate_game_state_info();
+        assert!(info.in_double_check);
+    }
+
     #[test]
     fn pawn_move_reset_halfmove_counter() {
         let game_state =
-            get_game_state_from_fen("rnbqkbnr/ppp1pppp/3p4/1B6/8/4P3/PPPP1PPP/RNBQK1NR b KQkq - 5");
+            get_game_state_from_fen("rnbqkbnr/ppp1pppp/3p4/1B6/8/4P3/PPPP1PPP/RNBQK1NR b KQkq - 5")
+                .unwrap();
         assert_eq!(game_state.halfmove_counter, 5);
+        // c7-c6 blocks the check
+        let game_state = game_state.perform_move(Move::new(10, 18));
+        assert_eq!(game_state.halfmove_counter, 0);
+    }
+
+    #[test]
+    fn halfmove_counter_past_255() {
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 255 200").unwrap();
+        let game_state = game_state.perform_move(Move::new(56, 48));
+        assert_eq!(game_state.halfmove_counter, 256);
+    }
+
+    #[test]
+    fn hash_matches_fen_after_moves() {
+        let game_state = GameState::default()
+            .perform_move(Move::two_square_pawn_move(52, 36))
+            .perform_move(Move::new(6, 21))
+            .perform_move(Move::two_square_pawn_move(51, 35));
+        let expected = get_game_state_from_fen(
+            "rnbqkb1r/pppppppp/5n2/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq d3 0 2",
+        )
+        .unwrap();
+        assert_eq!(game_state.hash, expected.hash);
+    }
+
+    #[test]
+    fn hash_transposition() {
+        let game_state = GameState::default()
+            .perform_move(Move::new(62, 45))
+            .perform_move(Move::new(6, 21))
+            .perform_move(Move::new(45, 62))
+            .perform_move(Move::new(21, 6));
+        assert_eq!(game_state.hash, GameState::default().hash);
+    }
+
+    #[test]
+    fn hash_depends_on_turn_castle_and_en_passant() {
+        let white = get_game_state_from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w Q d6 0 1").unwrap();
+        let black = get_game_state_from_fen("4k3/8/8/3pP3/8/8/8/R3K3 b Q d6 0 1").unwrap();
+        let no_castle = get_game_state_from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w - d6 0 1").unwrap();
+        let no_en_passant = get_game_state_from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert_ne!(white.hash, black.hash);
+        assert_ne!(white.hash, no_castle.hash);
+        assert_ne!(white.hash, no_en_passant.hash);
+    }
+
+    #[test]
+    fn hash_ignores_unusable_en_passant() {
+        let game_state = get_game_state_from_fen("4k3/8/8/8/3P4/8/8/4K3 b - d3 0 1").unwrap();
+        let no_en_passant = get_game_state_from_fen("4k3/8/8/8/3P4/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(game_state.hash, no_en_passant.hash);
+    }
+
+    #[test]
+    fn hash_after_castle_en_passant_and_promotion() {
+        let game_state = get_game_state_from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w Kq d6 0 1").unwrap();
+        for next_move in [
+            Move::castle(60, 62),
+            Move::en_passant(28, 19),
+            Move::promotion_capture(9, 0, Piece::Queen),
+        ] {
+            let next_state = game_state.perform_move(next_move);
+            assert_eq!(next_state.hash, next_state.compute_hash());
+        }
+    }
+
+    #[test]
+    fn parse_uci_move_sets_flags() {
+        let game_state = get_game_state_from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w Kq d6 0 1").unwrap();
+        assert_eq!(game_state.parse_uci_move("e1g1"), Ok(Move::castle(60, 62)));
+        assert_eq!(
+            game_state.parse_uci_move("e5d6"),
+            Ok(Move::en_passant(28, 19))
+        );
+        assert_eq!(
+            game_state.parse_uci_move("b7a8r"),
+            Ok(Move::promotion_capture(9, 0, Piece::Rook))
+        );
+        assert_eq!(
+            GameState::default().parse_uci_move("e2e4"),
+            Ok(Move::two_square_pawn_move(52, 36))
+        );
+    }
+
+    #[test]
+    fn parse_uci_move_round_trip() {
+        let game_state = get_game_state_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+        )
+        .unwrap();
+        for next_move in game_state.generate_legal_moves() {
+            assert_eq!(
+                game_state.parse_uci_move(&next_move.to_uci()),
+                Ok(next_move)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_uci_move_errors() {
+        let game_state = GameState::default();
+        for text in ["", "e2", "e2e4qq", "i2i4", "e0e1", "e2e4k", "e2-e4"] {
+            assert_eq!(
+                game_state.parse_uci_move(text),
+                Err(MoveParseError::InvalidFormat(text.to_string()))
+            );
+        }
+        for text in ["e2e5", "e1g1", "e7e5", "b1d2"] {
+            assert_eq!(
+                game_state.parse_uci_move(text),
+                Err(MoveParseError::IllegalMove(text.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn captures_and_quiet_moves_split_legal_moves() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Double check, only king moves
+            "4k3/8/8/8/8/4n3/8/4RK1r w - - 0 1",
+        ] {
+            let game_state = get_game_state_from_fen(fen).unwrap();
+            let captures = game_state.generate_legal_captures();
+            let quiet_moves = game_state.generate_legal_quiet_moves();
+            assert!(captures
+                .iter()
+                .all(|m| m.capture || m.promotion_piece.is_some()));
+            assert!(quiet_moves
+                .iter()
+                .all(|m| !m.capture && m.promotion_piece.is_none()));
+            let mut split: Vec<Move> = captures.into_iter().chain(quiet_moves).collect();
+            let mut moves = game_state.generate_legal_moves();
+            split.sort_by_key(|m| m.to_uci());
+            moves.sort_by_key(|m| m.to_uci());
+            assert_eq!(split, moves, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn en_passant_and_promotions_are_captures() {
+        let game_state = get_game_state_from_fen("4k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let captures: Vec<String> = game_state
+            .generate_legal_captures()
+            .iter()
+            .map(|m| m.to_uci())
+            .collect();
+        assert_eq!(captures, ["b7b8n", "b7b8b", "b7b8r", "b7b8q", "e5d6"]);
+    }
+
+    #[test]
+    fn is_legal() {
+        let game_state = GameState::default();
+        assert!(game_state.is_legal(Move::two_square_pawn_move(52, 36)));
+        assert!(game_state.is_legal(Move::new(62, 45)));
+        // Right squares, but missing the two square pawn move flag
+        assert!(!game_state.is_legal(Move::new(52, 36)));
+        assert!(!game_state.is_legal(Move::new(52, 28)));
+        // Pinned knight
+        let game_state = get_game_state_from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert!(!game_state.is_legal(Move::new(52, 37)));
+        assert!(game_state.is_legal(Move::new(60, 59)));
     }
 }
 
@@ -685,6 +1125,154 @@ mod perft_tests {
     use super::fen_util::*;
     use super::*;
 
+    const PERFT_FENS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    ];
+
+    // Makes and unmakes every move in the tree, checking that each unmake restores the position
+    // exactly
+    fn assert_make_unmake_identity(game_state: &mut GameState, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        for next_move in game_state.generate_legal_moves() {
+            let before = game_state.clone();
+            let undo_info = game_state.make_move(next_move);
+            assert_make_unmake_identity(game_state, depth - 1);
+            game_state.unmake_move(next_move, undo_info);
+            assert_eq!(*game_state, before, "after unmaking {:?}", next_move);
+        }
+    }
+
+    #[test]
+    fn perft_divide_pos_1_depth_3() {
+        let divide = GameState::default().perft_divide(3);
+        let expected = [
+            ("a2a3", 380),
+            ("a2a4", 420),
+            ("b1a3", 400),
+            ("b1c3", 440),
+            ("b2b3", 420),
+            ("b2b4", 421),
+            ("c2c3", 420),
+            ("c2c4", 441),
+            ("d2d3", 539),
+            ("d2d4", 560),
+            ("e2e3", 599),
+            ("e2e4", 600),
+            ("f2f3", 380),
+            ("f2f4", 401),
+            ("g1f3", 440),
+            ("g1h3", 400),
+            ("g2g3", 420),
+            ("g2g4", 421),
+            ("h2h3", 380),
+            ("h2h4", 420),
+        ];
+        let expected: Vec<(String, u64)> = expected
+            .iter()
+            .map(|&(uci, nodes)| (uci.to_string(), nodes))
+            .collect();
+        assert_eq!(divide, expected);
+    }
+
+    #[test]
+    fn perft_divide_sums_to_perft() {
+        for fen in PERFT_FENS {
+            let mut game_state = get_game_state_from_fen(fen).unwrap();
+            let divide = game_state.perft_divide(2);
+            let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+            assert_eq!(total, game_state.perft(2), "{}", fen);
+            assert_eq!(divide.len() as u64, game_state.perft(1), "{}", fen);
+        }
+        assert_eq!(GameState::default().perft_divide(0), vec![]);
+    }
+
+    #[test]
+    fn en_passant_captures_checking_pawn() {
+        let mut game_state = get_game_state_from_fen("1k6/8/8/5pP1/4K3/8/8/8 w - f6 0 1").unwrap();
+        assert!(game_state
+            .generate_legal_moves()
+            .contains(&Move::en_passant(30, 21)));
+        assert_eq!(game_state.perft(1), 9);
+        let mut game_state = get_game_state_from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        assert_eq!(game_state.perft(1), 9);
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // The king already stands on g1 and only the rook moves
+        let mut game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+        assert!(game_state.is_legal(Move::castle(62, 62)));
+        assert_eq!(game_state.move_to_uci(Move::castle(62, 62)), "g1h1");
+        assert_eq!(game_state.parse_uci_move("g1h1"), Ok(Move::castle(62, 62)));
+        let undo_info = game_state.make_move(Move::castle(62, 62));
+        assert_eq!(
+            game_state_to_fen_string(&game_state),
+            "4k3/8/8/8/8/8/8/5RK1 b - - 1 1"
+        );
+        game_state.unmake_move(Move::castle(62, 62), undo_info);
+        assert_eq!(
+            game_state_to_shredder_fen_string(&game_state),
+            "4k3/8/8/8/8/8/8/6KR w H - 0 1"
+        );
+
+        // The king and rook swap squares
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/5KR1 w G - 0 1").unwrap();
+        let castled = game_state.perform_move(game_state.parse_uci_move("f1g1").unwrap());
+        assert_eq!(
+            game_state_to_fen_string(&castled),
+            "4k3/8/8/8/8/8/8/5RK1 b - - 1 1"
+        );
+        assert_eq!(castled.hash, castled.compute_hash());
+
+        // Queenside, where b1c1 is a plain king move and the castle is written b1a1
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        assert_eq!(game_state.parse_uci_move("b1c1"), Ok(Move::new(57, 58)));
+        assert_eq!(game_state.parse_uci_move("b1a1"), Ok(Move::castle(57, 58)));
+        assert_eq!(
+            game_state_to_fen_string(&game_state.perform_move(Move::castle(57, 58))),
+            "4k3/8/8/8/8/8/8/2KR4 b - - 1 1"
+        );
+    }
+
+    #[test]
+    fn chess960_castling_rook_shields_king() {
+        // Once the rook leaves b1, the queen on a1 attacks the king on c1
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/qRK5 w B - 0 1").unwrap();
+        assert!(!game_state.generate_legal_moves().iter().any(|m| m.castle));
+        let game_state = get_game_state_from_fen("4k3/8/8/8/8/8/8/nRK5 w B - 0 1").unwrap();
+        assert!(game_state.is_legal(Move::castle(58, 58)));
+    }
+
+    #[test]
+    fn make_unmake_is_identity() {
+        for fen in PERFT_FENS {
+            let mut game_state = get_game_state_from_fen(fen).unwrap();
+            assert_make_unmake_identity(&mut game_state, 3);
+        }
+    }
+
+    #[test]
+    fn make_unmake_pseudo_legal_moves() {
+        // Moves that leave the king in check must be taken back just as well, since callers may
+        // make them to test for legality
+        for fen in PERFT_FENS {
+            let mut game_state = get_game_state_from_fen(fen).unwrap();
+            let before = game_state.clone();
+            for next_move in game_state.generate_pseudo_legal_moves(false) {
+                let undo_info = game_state.make_move(next_move);
+                game_state.unmake_move(next_move, undo_info);
+                assert_eq!(game_state, before, "after unmaking {:?}", next_move);
+            }
+        }
+    }
+
     #[test]
     fn perft_pos_1_depth_1() {
         let moves = GameState::default().generate_legal_states();
@@ -693,148 +1281,149 @@ mod perft_tests {
 
     #[test]
     fn perft_pos_1_depth_2() {
-        let game_states = GameState::default().generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 400);
+        assert_eq!(GameState::default().perft(2), 400);
     }
 
     #[test]
     fn perft_pos_1_depth_3() {
-        let game_states = GameState::default().generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 8902);
+        assert_eq!(GameState::default().perft(3), 8902);
     }
 
     #[test]
     fn perft_pos_2_depth_1() {
         let game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        );
+        )
+        .unwrap();
         let moves = game_state.generate_legal_states();
         assert_eq!(moves.len(), 48);
     }
 
     #[test]
     fn perft_pos_2_depth_2() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 2039);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(2), 2039);
     }
 
     #[test]
     fn perft_pos_2_depth_3() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 97862);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(3), 97862);
     }
 
     #[test]
     fn perft_pos_3_depth_1() {
-        let game_state = get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -");
+        let game_state = get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -").unwrap();
         let moves = game_state.generate_legal_states();
         assert_eq!(moves.len(), 14);
     }
 
     #[test]
     fn perft_pos_3_depth_2() {
-        let game_state = get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -");
-        let game_states = game_state.generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 191);
+        let mut game_state =
+            get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -").unwrap();
+        assert_eq!(game_state.perft(2), 191);
     }
 
     #[test]
     fn perft_pos_3_depth_3() {
-        let game_state = get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -");
-        let game_states = game_state.generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 2812);
+        let mut game_state =
+            get_game_state_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -").unwrap();
+        assert_eq!(game_state.perft(3), 2812);
     }
 
     #[test]
     fn perft_pos_4_depth_1() {
         let game_state = get_game_state_from_fen(
             "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-        );
+        )
+        .unwrap();
         let moves = game_state.generate_legal_states();
         assert_eq!(moves.len(), 6);
     }
 
     #[test]
     fn perft_pos_4_depth_2() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 264);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(2), 264);
     }
 
     #[test]
     fn perft_pos_4_depth_3() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 9467);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(3), 9467);
     }
 
     #[test]
     fn perft_pos_5_depth_1() {
         let game_state =
-            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
+            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
+                .unwrap();
         let moves = game_state.generate_legal_states();
         assert_eq!(moves.len(), 44);
     }
 
     #[test]
     fn perft_pos_5_depth_2() {
-        let game_state =
-            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
-        let game_states = game_state.generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 1486);
+        let mut game_state =
+            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
+                .unwrap();
+        assert_eq!(game_state.perft(2), 1486);
     }
 
     #[test]
     fn perft_pos_5_depth_3() {
-        let game_state =
-            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
-        let game_states = game_state.generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 62379);
+        let mut game_state =
+            get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
+                .unwrap();
+        assert_eq!(game_state.perft(3), 62379);
     }
 
     // passes, but slow
     // #[test]
     // fn perft_pos_5_depth_4() {
-    //   let game_state = get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
-    //   let game_states = game_state.generate_legal_moves_at_depth(4);
-    //   assert_eq!(game_states.len(), 2_103_487 );
+    //   let mut game_state = get_game_state_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+    //   assert_eq!(game_state.perft(4), 2_103_487);
     // }
 
     #[test]
     fn perft_pos_6_depth_1() {
         let game_state = get_game_state_from_fen(
             "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
-        );
+        )
+        .unwrap();
         let moves = game_state.generate_legal_states();
         assert_eq!(moves.len(), 46);
     }
 
     #[test]
     fn perft_pos_6_depth_2() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(2);
-        assert_eq!(game_states.len(), 2079);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(2), 2079);
     }
 
     #[test]
     fn perft_pos_6_depth_3() {
-        let game_state = get_game_state_from_fen(
+        let mut game_state = get_game_state_from_fen(
             "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
-        );
-        let game_states = game_state.generate_legal_moves_at_depth(3);
-        assert_eq!(game_states.len(), 89890);
+        )
+        .unwrap();
+        assert_eq!(game_state.perft(3), 89890);
     }
 }
 
@@ -842,7 +1431,7 @@ mod perft_tests {
 mod benchmark_tests {
     extern crate test;
 
-    use super::{fen_util::*, GameState, Move, GLOBAL_WITH_INFO};
+    use super::{fen_util::*, GameState, Move};
     use test::Bencher;
 
     #[bench]
@@ -851,10 +1440,20 @@ mod benchmark_tests {
         b.iter(|| game_state.perform_move(Move::new(52, 36)));
     }
 
+    #[bench]
+    fn make_unmake_move(b: &mut Bencher) {
+        let mut game_state = GameState::default();
+        let next_move = Move::new(52, 36);
+        b.iter(|| {
+            let undo_info = game_state.make_move(next_move);
+            game_state.unmake_move(next_move, undo_info);
+        });
+    }
+
     #[bench]
     fn start_pos_pseudo_bench(b: &mut Bencher) {
         let game_state = GameState::default();
-        b.iter(|| game_state.generate_pseudo_legal_moves(GLOBAL_WITH_INFO));
+        b.iter(|| game_state.generate_legal_moves());
     }
 
     #[bench]
@@ -867,15 +1466,17 @@ mod benchmark_tests {
     fn perft_pos_2_pseudo_bench(b: &mut Bencher) {
         let game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        );
-        b.iter(|| game_state.generate_pseudo_legal_moves(GLOBAL_WITH_INFO));
+        )
+        .unwrap();
+        b.iter(|| game_state.generate_legal_moves());
     }
 
     #[bench]
     fn perft_pos_2_legal_bench(b: &mut Bencher) {
         let game_state = get_game_state_from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
-        );
+        )
+        .unwrap();
         b.iter(|| game_state.generate_legal_states());
     }
 
@@ -0,0 +1,736 @@
+use std::fmt::Display;
+
+use super::{
+    fen_util::{
+        game_state_to_fen_string, get_validated_chess960_game_state_from_fen,
+        get_validated_game_state_from_fen, FenError,
+    },
+    game::{Game, GameStatus},
+    types::*,
+    GameState,
+};
+
+// Export format lines are kept below 80 characters
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PgnResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    // Game still in progress, abandoned or result otherwise unknown
+    Unknown,
+}
+
+impl PgnResult {
+    fn from_token(token: &str) -> Option<PgnResult> {
+        match token {
+            "1-0" => Some(PgnResult::WhiteWins),
+            "0-1" => Some(PgnResult::BlackWins),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PgnResult::WhiteWins => "1-0",
+            PgnResult::BlackWins => "0-1",
+            PgnResult::Draw => "1/2-1/2",
+            PgnResult::Unknown => "*",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    // Tag pairs in the order they appeared
+    pub tags: Vec<(String, String)>,
+    // Position before the first move, taken from the FEN tag if there is one
+    pub start: GameState,
+    pub line: PgnLine,
+    pub result: PgnResult,
+}
+
+// A sequence of moves, either the main line of a game or a variation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnLine {
+    // Comment before the first move
+    pub comment: Option<String>,
+    pub moves: Vec<PgnNode>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnNode {
+    pub next_move: Move,
+    // Position after the move
+    pub game_state: GameState,
+    // Numeric Annotation Glyphs, e.g. 1 for "!" or 4 for "??"
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    // Alternatives to this move, played from the position before it
+    pub variations: Vec<PgnLine>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnError {
+    InvalidTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    UnexpectedToken(String),
+    InvalidMove(MoveParseError),
+    InvalidFen(FenError),
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag pair: {}", tag),
+            PgnError::UnterminatedComment => write!(f, "comment is missing a closing brace"),
+            PgnError::UnbalancedVariation => write!(f, "unbalanced parentheses in variation"),
+            PgnError::UnexpectedToken(token) => write!(f, "unexpected token: {}", token),
+            PgnError::InvalidMove(error) => write!(f, "{}", error),
+            PgnError::InvalidFen(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Nag(u8),
+    MoveNumber,
+    Result(PgnResult),
+    San(String),
+}
+
+impl PgnGame {
+    // The game's moves with the Seven Tag Roster left unknown, plus the tags needed to set up a
+    // start position other than the standard one
+    pub fn from_game(game: &Game) -> PgnGame {
+        // A claimable draw is not a result until a player claims it
+        let status = game.status();
+        let result = match status {
+            GameStatus::Checkmate(Color::White) => PgnResult::WhiteWins,
+            GameStatus::Checkmate(Color::Black) => PgnResult::BlackWins,
+            _ if status.is_game_over() => PgnResult::Draw,
+            _ => PgnResult::Unknown,
+        };
+        let mut tags: Vec<(String, String)> = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", result.as_str()),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        let start = game.start();
+        if start.chess960 {
+            tags.push(("Variant".to_string(), "Chess960".to_string()));
+        }
+        if start.chess960 || *start != GameState::default() {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), game_state_to_fen_string(start)));
+        }
+        let mut game_state = start.clone();
+        let moves = game
+            .moves()
+            .iter()
+            .map(|&next_move| {
+                game_state = game_state.perform_move(next_move);
+                PgnNode {
+                    next_move,
+                    game_state: game_state.clone(),
+                    nags: Vec::new(),
+                    comment: None,
+                    variations: Vec::new(),
+                }
+            })
+            .collect();
+        PgnGame {
+            tags,
+            start: start.clone(),
+            line: PgnLine {
+                comment: None,
+                moves,
+            },
+            result,
+        }
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The moves of the main line, without variations
+    pub fn get_mainline_moves(&self) -> Vec<Move> {
+        self.line.moves.iter().map(|node| node.next_move).collect()
+    }
+
+    // Writes the game in PGN export format
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        if !self.tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        let mut tokens = Vec::new();
+        write_line(
+            &self.line,
+            &self.start,
+            self.start.fullmove_number,
+            &mut tokens,
+        );
+        tokens.push(self.result.as_str().to_string());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push('\n');
+        pgn
+    }
+}
+
+// Parses every game in a PGN file
+pub fn parse_pgn(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(pgn)?;
+    let mut position = 0;
+    let mut games = Vec::new();
+    while position < tokens.len() {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(name, value)) = tokens.get(position) {
+            tags.push((name.clone(), value.clone()));
+            position += 1;
+        }
+        // Chess960 games give their start position in X-FEN
+        let chess960 = tags
+            .iter()
+            .any(|(name, value)| name == "Variant" && value.eq_ignore_ascii_case("chess960"));
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) if chess960 => {
+                get_validated_chess960_game_state_from_fen(fen).map_err(PgnError::InvalidFen)?
+            }
+            Some((_, fen)) => {
+                get_validated_game_state_from_fen(fen).map_err(PgnError::InvalidFen)?
+            }
+            None => GameState {
+                chess960,
+                ..Default::default()
+            },
+        };
+        let line = parse_line(&tokens, &mut position, &start, false)?;
+        let result = match tokens.get(position) {
+            Some(Token::Result(result)) => {
+                position += 1;
+                *result
+            }
+            _ => PgnResult::Unknown,
+        };
+        games.push(PgnGame {
+            tags,
+            start,
+            line,
+            result,
+        });
+    }
+    Ok(games)
+}
+
+pub fn write_pgn(games: &[PgnGame]) -> String {
+    games
+        .iter()
+        .map(|game| game.to_pgn())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_line(
+    tokens: &[Token],
+    position: &mut usize,
+    start: &GameState,
+    is_variation: bool,
+) -> Result<PgnLine, PgnError> {
+    let mut line = PgnLine::default();
+    loop {
+        match tokens.get(*position) {
+            None | Some(Token::Tag(..)) | Some(Token::Result(_)) if !is_variation => break,
+            None | Some(Token::Tag(..)) | Some(Token::Result(_)) => {
+                return Err(PgnError::UnbalancedVariation)
+            }
+            Some(Token::VariationEnd) if is_variation => {
+                *position += 1;
+                break;
+            }
+            Some(Token::VariationEnd) => return Err(PgnError::UnbalancedVariation),
+            Some(Token::MoveNumber) => *position += 1,
+            Some(Token::San(san)) => {
+                let game_state = line.moves.last().map_or(start, |node| &node.game_state);
+                let next_move = game_state.parse_san(san).map_err(PgnError::InvalidMove)?;
+                line.moves.push(PgnNode {
+                    next_move,
+                    game_state: game_state.perform_move(next_move),
+                    nags: Vec::new(),
+                    comment: None,
+                    variations: Vec::new(),
+                });
+                *position += 1;
+            }
+            Some(Token::Nag(nag)) => {
+                let node = line
+                    .moves
+                    .last_mut()
+                    .ok_or_else(|| PgnError::UnexpectedToken(format!("${}", nag)))?;
+                node.nags.push(*nag);
+                *position += 1;
+            }
+            Some(Token::Comment(text)) => {
+                let comment = match line.moves.last_mut() {
+                    Some(node) => &mut node.comment,
+                    None => &mut line.comment,
+                };
+                // Consecutive comments are merged into one
+                *comment = Some(match comment.take() {
+                    Some(existing) => format!("{} {}", existing, text),
+                    None => text.clone(),
+                });
+                *position += 1;
+            }
+            Some(Token::VariationStart) => {
+                // A variation replaces the last move, so it starts from the position before it
+                let variation_start = match line.moves.len() {
+                    0 => return Err(PgnError::UnexpectedToken("(".to_string())),
+                    1 => start,
+                    length => &line.moves[length - 2].game_state,
+                };
+                *position += 1;
+                let variation = parse_line(tokens, position, variation_start, true)?;
+                line.moves.last_mut().unwrap().variations.push(variation);
+            }
+        }
+    }
+    Ok(line)
+}
+
+fn write_line(line: &PgnLine, start: &GameState, fullmove: u32, tokens: &mut Vec<String>) {
+    if let Some(comment) = &line.comment {
+        tokens.push(format!("{{{}}}", comment));
+    }
+    let mut game_state = start;
+    let mut fullmove = fullmove;
+    // Black moves only need a move number at the start of a line or after a comment or variation
+    let mut needs_move_number = true;
+    for node in &line.moves {
+        if game_state.turn == Color::White {
+            tokens.push(format!("{}.", fullmove));
+        } else if needs_move_number {
+            tokens.push(format!("{}...", fullmove));
+        }
+        tokens.push(game_state.move_to_san(node.next_move));
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &node.comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        for variation in &node.variations {
+            let mut variation_tokens = Vec::new();
+            write_line(variation, game_state, fullmove, &mut variation_tokens);
+            match variation_tokens.as_mut_slice() {
+                [] => tokens.push("()".to_string()),
+                [first, .., last] => {
+                    first.insert(0, '(');
+                    last.push(')');
+                }
+                [only] => *only = format!("({})", only),
+            }
+            tokens.append(&mut variation_tokens);
+        }
+        needs_move_number = node.comment.is_some() || !node.variations.is_empty();
+        if game_state.turn == Color::Black {
+            fullmove += 1;
+        }
+        game_state = &node.game_state;
+    }
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Token>, PgnError> {
+    let chars: Vec<char> = pgn.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let at_line_start = index == 0 || chars[index - 1] == '\n';
+        match chars[index] {
+            // Lines starting with % are escaped and ignored
+            '%' if at_line_start => index = find_line_end(&chars, index),
+            c if c.is_whitespace() => index += 1,
+            ';' => {
+                let end = find_line_end(&chars, index);
+                tokens.push(get_comment_token(&chars[index + 1..end]));
+                index = end;
+            }
+            '{' => {
+                let end = chars[index..]
+                    .iter()
+                    .position(|&c| c == '}')
+                    .ok_or(PgnError::UnterminatedComment)?
+                    + index;
+                tokens.push(get_comment_token(&chars[index + 1..end]));
+                index = end + 1;
+            }
+            '[' => {
+                let end = find_line_end(&chars, index);
+                let (tag, tag_end) = parse_tag(&chars, index + 1).ok_or_else(|| {
+                    PgnError::InvalidTag(chars[index..end].iter().collect::<String>())
+                })?;
+                tokens.push(tag);
+                index = tag_end;
+            }
+            '(' => {
+                tokens.push(Token::VariationStart);
+                index += 1;
+            }
+            ')' => {
+                tokens.push(Token::VariationEnd);
+                index += 1;
+            }
+            _ => {
+                let end = chars[index + 1..]
+                    .iter()
+                    .position(|&c| c.is_whitespace() || "{}()[];".contains(c))
+                    .map_or(chars.len(), |length| index + 1 + length);
+                let symbol: String = chars[index..end].iter().collect();
+                push_symbol_tokens(&symbol, &mut tokens)?;
+                index = end;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn find_line_end(chars: &[char], index: usize) -> usize {
+    chars[index..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |length| index + length)
+}
+
+// Comments are written on one line, so line breaks and indentation inside them are collapsed
+fn get_comment_token(chars: &[char]) -> Token {
+    let text: String = chars.iter().collect();
+    Token::Comment(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// Parses `Name "value"]` starting after the opening bracket. Returns the token and the index after
+// the closing bracket.
+fn parse_tag(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let skip_whitespace = |index: usize| {
+        (index..chars.len())
+            .find(|&i| !chars[i].is_whitespace())
+            .unwrap_or(chars.len())
+    };
+    let name_start = skip_whitespace(start);
+    let name_end = (name_start..chars.len())
+        .find(|&i| !(chars[i].is_ascii_alphanumeric() || chars[i] == '_'))
+        .unwrap_or(chars.len());
+    if name_start == name_end {
+        return None;
+    }
+    let mut index = skip_whitespace(name_end);
+    if chars.get(index) != Some(&'"') {
+        return None;
+    }
+    index += 1;
+    let mut value = String::new();
+    loop {
+        match chars.get(index)? {
+            '"' => break,
+            '\\' => {
+                index += 1;
+                value.push(*chars.get(index)?);
+            }
+            '\n' => return None,
+            &c => value.push(c),
+        }
+        index += 1;
+    }
+    index = skip_whitespace(index + 1);
+    if chars.get(index) != Some(&']') {
+        return None;
+    }
+    let name = chars[name_start..name_end].iter().collect();
+    Some((Token::Tag(name, value), index + 1))
+}
+
+fn push_symbol_tokens(symbol: &str, tokens: &mut Vec<Token>) -> Result<(), PgnError> {
+    let unexpected_token = || PgnError::UnexpectedToken(symbol.to_string());
+    if let Some(result) = PgnResult::from_token(symbol) {
+        tokens.push(Token::Result(result));
+        return Ok(());
+    }
+    if let Some(nag) = symbol.strip_prefix('$') {
+        tokens.push(Token::Nag(nag.parse().map_err(|_| unexpected_token())?));
+        return Ok(());
+    }
+    // The optional en passant marker after a capture like exd6 carries no information
+    if symbol == "e.p." {
+        return Ok(());
+    }
+
+    let mut san = symbol;
+    // Move numbers may be followed by the move without a space, like 1.e4 or 12...Nf6. Castling
+    // written with zeros is not a move number.
+    if !san.starts_with("0-0") {
+        let digits_end = san.find(|c: char| !c.is_ascii_digit()).unwrap_or(san.len());
+        if digits_end > 0 {
+            let after_dots = san[digits_end..].trim_start_matches('.');
+            if after_dots.len() == san.len() - digits_end {
+                return Err(unexpected_token());
+            }
+            tokens.push(Token::MoveNumber);
+            san = after_dots;
+            if san.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    // Move suffix annotations are stored as the equivalent NAG
+    let (san, annotation) = san.split_at(san.find(['!', '?']).unwrap_or(san.len()));
+    if san.is_empty() {
+        return Err(unexpected_token());
+    }
+    tokens.push(Token::San(san.to_string()));
+    let nag = match annotation {
+        "" => return Ok(()),
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return Err(unexpected_token()),
+    };
+    tokens.push(Token::Nag(nag));
+    Ok(())
+}
+
+#[cfg(test)]
+mod pgn_tests {
+    use super::super::fen_util::get_game_state_from_fen;
+    use super::super::validation::PositionProblem;
+    use super::*;
+
+    const CLASSIC_GAMES: &str = include_str!("test_data/classic_games.pgn");
+    const ANNOTATED_GAMES: &str = include_str!("test_data/annotated_games.pgn");
+
+    #[test]
+    fn round_trip_classic_games() {
+        let games = parse_pgn(CLASSIC_GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(write_pgn(&games), CLASSIC_GAMES);
+
+        let opera_game = &games[0];
+        assert_eq!(opera_game.get_tag("White"), Some("Paul Morphy"));
+        assert_eq!(opera_game.result, PgnResult::WhiteWins);
+        assert_eq!(opera_game.line.moves.len(), 33);
+        let queenside_castle = &opera_game.line.moves[22];
+        assert_eq!(queenside_castle.next_move, Move::castle(60, 58));
+        let last_state = &opera_game.line.moves.last().unwrap().game_state;
+        assert!(last_state.is_in_check());
+        assert!(last_state.generate_legal_moves().is_empty());
+    }
+
+    #[test]
+    fn round_trip_annotated_games() {
+        let games = parse_pgn(ANNOTATED_GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(write_pgn(&games), ANNOTATED_GAMES);
+        assert_eq!(parse_pgn(&write_pgn(&games)).unwrap(), games);
+
+        let sicilian = &games[0];
+        assert_eq!(sicilian.result, PgnResult::Draw);
+        assert_eq!(
+            sicilian.line.comment.as_deref(),
+            Some("An opening with several sidelines.")
+        );
+        let c5 = &sicilian.line.moves[1];
+        assert_eq!(c5.nags, vec![1]);
+        assert_eq!(c5.variations.len(), 1);
+        let open_game = &c5.variations[0].moves;
+        assert_eq!(open_game[0].next_move, Move::two_square_pawn_move(12, 28));
+        assert_eq!(
+            open_game[2].variations[0].moves[0].comment.as_deref(),
+            Some("Petrov")
+        );
+
+        let study = &games[1];
+        assert_eq!(study.result, PgnResult::Unknown);
+        assert_eq!(study.get_tag("SetUp"), Some("1"));
+        assert_eq!(study.line.moves[0].next_move, Move::en_passant(28, 19));
+        assert_eq!(
+            study.get_mainline_moves().last(),
+            Some(&Move::promotion(11, 3, Piece::Knight))
+        );
+    }
+
+    #[test]
+    fn parse_chess960_game() {
+        let pgn = "[Variant \"Chess960\"]\n\
+            [SetUp \"1\"]\n\
+            [FEN \"qnbbrnkr/pppppppp/8/8/8/8/PPPPPPPP/QNBBRNKR w KQkq - 0 1\"]\n\
+            \n\
+            1. Ng3 Ng6 2. O-O O-O *\n";
+        let games = parse_pgn(pgn).unwrap();
+        assert!(games[0].start.chess960);
+        let moves = games[0].get_mainline_moves();
+        assert_eq!(moves[2], Move::castle(62, 62));
+        assert_eq!(moves[3], Move::castle(6, 6));
+        assert_eq!(write_pgn(&games), pgn);
+    }
+
+    #[test]
+    fn from_game() {
+        let mut game = Game::default();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            game.play_move(game.current().parse_san(san).unwrap());
+        }
+        assert_eq!(
+            PgnGame::from_game(&game).to_pgn(),
+            "[Event \"?\"]\n\
+            [Site \"?\"]\n\
+            [Date \"????.??.??\"]\n\
+            [Round \"?\"]\n\
+            [White \"?\"]\n\
+            [Black \"?\"]\n\
+            [Result \"0-1\"]\n\
+            \n\
+            1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+
+        let start = get_game_state_from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        let mut game = Game::new(start);
+        game.play_move(game.current().parse_san("Kd7").unwrap());
+        let games = parse_pgn(&PgnGame::from_game(&game).to_pgn()).unwrap();
+        assert_eq!(games[0].get_tag("Result"), Some("*"));
+        assert_eq!(games[0].start, *game.start());
+        assert_eq!(games[0].get_mainline_moves(), game.moves());
+
+        let mut game = Game::default();
+        let knight_dance = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        for san in knight_dance.repeat(2) {
+            game.play_move(game.current().parse_san(san).unwrap());
+        }
+        assert_eq!(game.status(), GameStatus::ThreefoldRepetition);
+        assert_eq!(PgnGame::from_game(&game).get_tag("Result"), Some("*"));
+        for san in knight_dance.repeat(2) {
+            game.play_move(game.current().parse_san(san).unwrap());
+        }
+        assert_eq!(game.status(), GameStatus::FivefoldRepetition);
+        assert_eq!(PgnGame::from_game(&game).get_tag("Result"), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn parse_import_format_variants() {
+        let pgn = "% escaped line\n\
+            [Event \"Casual\"]\n\
+            [White \"Quote \\\"Q\\\" and backslash \\\\\"]\n\
+            \n\
+            1.e4 e5 2.Nf3!? ; rest of line comment\n\
+            Nc6 3.Bb5 a6 {multi\n   line comment} 4.Ba4 Nf6 5.0-0 *\n";
+        let games = parse_pgn(pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(
+            games[0].get_tag("White"),
+            Some("Quote \"Q\" and backslash \\")
+        );
+        assert_eq!(
+            games[0].to_pgn(),
+            "[Event \"Casual\"]\n\
+            [White \"Quote \\\"Q\\\" and backslash \\\\\"]\n\
+            \n\
+            1. e4 e5 2. Nf3 $5 {rest of line comment} 2... Nc6 3. Bb5 a6\n\
+            {multi line comment} 4. Ba4 Nf6 5. O-O *\n"
+        );
+    }
+
+    #[test]
+    fn parse_games_without_result() {
+        let pgn = "[Event \"A\"]\n\n1. e4\n\n[Event \"B\"]\n\n1. d4 d5 0-1\n";
+        let games = parse_pgn(pgn).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, PgnResult::Unknown);
+        assert_eq!(games[0].get_mainline_moves().len(), 1);
+        assert_eq!(games[1].result, PgnResult::BlackWins);
+        assert_eq!(games[1].get_mainline_moves().len(), 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse_pgn("1. e4 {unterminated"),
+            Err(PgnError::UnterminatedComment)
+        );
+        assert_eq!(
+            parse_pgn("1. e4 (1. d4 *"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(parse_pgn("1. e4 ) *"), Err(PgnError::UnbalancedVariation));
+        assert_eq!(
+            parse_pgn("1. e5 *"),
+            Err(PgnError::InvalidMove(MoveParseError::IllegalMove(
+                "e5".to_string()
+            )))
+        );
+        assert_eq!(
+            parse_pgn("[Event]\n1. e4 *"),
+            Err(PgnError::InvalidTag("[Event]".to_string()))
+        );
+        assert_eq!(
+            parse_pgn("1. e4 $x *"),
+            Err(PgnError::UnexpectedToken("$x".to_string()))
+        );
+        assert_eq!(
+            parse_pgn("[FEN \"8/8/8/8 w - - 0 1\"]\n*"),
+            Err(PgnError::InvalidFen(FenError::WrongRankCount { offset: 7 }))
+        );
+        assert_eq!(
+            parse_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n1. e4 *"),
+            Err(PgnError::InvalidFen(FenError::IllegalPosition {
+                problems: vec![
+                    PositionProblem::MissingKing(Color::White),
+                    PositionProblem::MissingKing(Color::Black),
+                ]
+            }))
+        );
+        assert_eq!(
+            parse_pgn("(1. e4) *"),
+            Err(PgnError::UnexpectedToken("(".to_string()))
+        );
+    }
+}
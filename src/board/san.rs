@@ -0,0 +1,346 @@
+use super::{fen_util::get_square_from_index, parse_square, types::*, GameState};
+
+// A SAN move broken into its parts before it is matched against the legal moves
+struct SanParts {
+    piece: Piece,
+    from_file: Option<usize>,
+    from_rank: Option<usize>,
+    to: usize,
+    promotion_piece: Option<Piece>,
+}
+
+impl GameState {
+    // Standard Algebraic Notation, e.g. Nbd7, exd6, O-O-O, e8=Q+ or Qxf7#. En passant captures are
+    // written without the optional "e.p." so the output can be used in PGN movetext.
+    pub fn move_to_san(&self, next_move: Move) -> String {
+        self.get_san(next_move, false)
+    }
+
+    // The same as move_to_san, but marks en passant captures for display, e.g. exd6 e.p.
+    pub fn move_to_display_san(&self, next_move: Move) -> String {
+        self.get_san(next_move, true)
+    }
+
+    fn get_san(&self, next_move: Move, mark_en_passant: bool) -> String {
+        let mut san = if next_move.castle {
+            // The king lands on the g-file when castling kingside and on the c-file otherwise
+            if next_move.to % 8 == 6 {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            self.get_san_without_suffix(next_move)
+        };
+        if mark_en_passant && next_move.en_passant {
+            san.push_str(" e.p.");
+        }
+
+        let next_state = self.perform_move(next_move);
+        if next_state.is_in_check() {
+            if next_state.generate_legal_moves().is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+        san
+    }
+
+    fn get_san_without_suffix(&self, next_move: Move) -> String {
+        let Move { from, to, .. } = next_move;
+        let (_, piece) = self.board.get_square(from);
+        let from_square = get_square_from_index(from);
+        let mut san = String::new();
+        if piece == Piece::Pawn {
+            if next_move.capture {
+                san.push_str(&from_square[..1]);
+            }
+        } else {
+            san.push(get_san_piece_char(piece));
+            // Only legal moves need to be told apart, so a pinned piece never adds a disambiguation
+            let others: Vec<usize> = self
+                .generate_legal_moves()
+                .iter()
+                .filter(|m| m.to == to && m.from != from && !m.castle)
+                .filter(|m| self.board.get_square(m.from).1 == piece)
+                .map(|m| m.from)
+                .collect();
+            if !others.is_empty() {
+                if others.iter().all(|&other| other % 8 != from % 8) {
+                    san.push_str(&from_square[..1]);
+                } else if others.iter().all(|&other| other / 8 != from / 8) {
+                    san.push_str(&from_square[1..]);
+                } else {
+                    san.push_str(&from_square);
+                }
+            }
+        }
+        if next_move.capture {
+            san.push('x');
+        }
+        san.push_str(&get_square_from_index(to));
+        if let Some(promotion_piece) = next_move.promotion_piece {
+            san.push('=');
+            san.push(get_san_piece_char(promotion_piece));
+        }
+        san
+    }
+
+    // Parses Standard Algebraic Notation. Common variants are accepted as well: castling with zeros,
+    // a missing or extra capture sign, long algebraic like Ng1-f3, promotion without "=", an "e.p."
+    // suffix and trailing check, mate or annotation symbols like + # ! ?
+    pub fn parse_san(&self, text: &str) -> Result<Move, MoveParseError> {
+        let san = text.trim();
+        // The check sign may come before or after the en passant marker
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = self.generate_legal_moves();
+        let candidates: Vec<Move> = match san {
+            "O-O" | "0-0" => legal_moves
+                .into_iter()
+                .filter(|m| m.castle && m.to % 8 == 6)
+                .collect(),
+            "O-O-O" | "0-0-0" => legal_moves
+                .into_iter()
+                .filter(|m| m.castle && m.to % 8 == 2)
+                .collect(),
+            _ => {
+                let parts = parse_san_parts(san)
+                    .ok_or_else(|| MoveParseError::InvalidFormat(text.to_string()))?;
+                legal_moves
+                    .into_iter()
+                    .filter(|m| {
+                        !m.castle
+                            && m.to == parts.to
+                            && m.promotion_piece == parts.promotion_piece
+                            && self.board.get_square(m.from).1 == parts.piece
+                            && parts.from_file.is_none_or(|file| m.from % 8 == file)
+                            && parts.from_rank.is_none_or(|rank| m.from / 8 == rank)
+                    })
+                    .collect()
+            }
+        };
+        match candidates.as_slice() {
+            [next_move] => Ok(*next_move),
+            [] => Err(MoveParseError::IllegalMove(text.to_string())),
+            _ => Err(MoveParseError::AmbiguousMove(text.to_string())),
+        }
+    }
+}
+
+fn parse_san_parts(san: &str) -> Option<SanParts> {
+    let mut chars: Vec<char> = san
+        .chars()
+        .filter(|c| !matches!(c, 'x' | ':' | '-' | '='))
+        .collect();
+
+    let piece = match chars.first().and_then(|&c| get_piece_from_san_char(c)) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Piece::Pawn,
+    };
+
+    // Promotions are usually written e8=Q, but e8Q and e8q are common as well
+    let mut promotion_piece = None;
+    if let [.., rank, last] = chars.as_slice() {
+        let promotion = get_piece_from_san_char(last.to_ascii_uppercase())
+            .filter(|&promotion| promotion != Piece::King);
+        if piece == Piece::Pawn && rank.is_ascii_digit() && promotion.is_some() {
+            promotion_piece = promotion;
+            chars.pop();
+        }
+    }
+
+    let [disambiguation @ .., to_file, to_rank] = chars.as_slice() else {
+        return None;
+    };
+    let to = parse_square(*to_file, *to_rank)?;
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in disambiguation {
+        match c {
+            'a'..='h' if from_file.is_none() => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' if from_rank.is_none() => from_rank = Some('8' as usize - c as usize),
+            _ => return None,
+        }
+    }
+    Some(SanParts {
+        piece,
+        from_file,
+        from_rank,
+        to,
+        promotion_piece,
+    })
+}
+
+fn get_san_piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+        _ => unreachable!("{:?} has no SAN letter", piece),
+    }
+}
+
+fn get_piece_from_san_char(c: char) -> Option<Piece> {
+    match c {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod san_tests {
+    use super::super::fen_util::get_game_state_from_fen;
+    use super::*;
+
+    fn uci_to_san(fen: &str, uci: &str) -> String {
+        let game_state = get_game_state_from_fen(fen).unwrap();
+        let next_move = game_state.parse_uci_move(uci).unwrap();
+        game_state.move_to_san(next_move)
+    }
+
+    fn san_to_uci(fen: &str, san: &str) -> Result<String, MoveParseError> {
+        let game_state = get_game_state_from_fen(fen).unwrap();
+        game_state.parse_san(san).map(|m| m.to_uci())
+    }
+
+    #[test]
+    fn san_pawn_and_piece_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(uci_to_san(fen, "e2e4"), "e4");
+        assert_eq!(uci_to_san(fen, "g1f3"), "Nf3");
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        assert_eq!(
+            uci_to_san("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2"),
+            "Nbd2"
+        );
+        assert_eq!(
+            uci_to_san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"),
+            "R1a3"
+        );
+        assert_eq!(
+            uci_to_san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3"),
+            "R5a3"
+        );
+        assert_eq!(
+            uci_to_san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"),
+            "Qa1b2"
+        );
+        // The knight on e3 is pinned, so it does not need to be told apart
+        assert_eq!(
+            uci_to_san("4k3/4r3/8/8/8/2N1N3/8/4K3 w - - 0 1", "c3d5"),
+            "Nd5"
+        );
+    }
+
+    #[test]
+    fn san_special_moves() {
+        let castle_fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(uci_to_san(castle_fen, "e1g1"), "O-O");
+        assert_eq!(uci_to_san(castle_fen, "e1c1"), "O-O-O");
+        assert_eq!(
+            uci_to_san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+            "exd6"
+        );
+        let en_passant_check =
+            get_game_state_from_fen("8/4k3/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let next_move = en_passant_check.parse_uci_move("e5d6").unwrap();
+        assert_eq!(en_passant_check.move_to_san(next_move), "exd6+");
+        assert_eq!(
+            en_passant_check.move_to_display_san(next_move),
+            "exd6 e.p.+"
+        );
+        assert_eq!(en_passant_check.parse_san("exd6 e.p.+"), Ok(next_move));
+        assert_eq!(en_passant_check.parse_san("exd6+ e.p."), Ok(next_move));
+        assert_eq!(
+            uci_to_san("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"),
+            "e8=Q+"
+        );
+        assert_eq!(
+            uci_to_san("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"),
+            "e8=N"
+        );
+    }
+
+    #[test]
+    fn san_mate() {
+        let scholars_mate = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        assert_eq!(uci_to_san(scholars_mate, "h5f7"), "Qxf7#");
+        assert_eq!(
+            uci_to_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"),
+            "Ra8#"
+        );
+    }
+
+    #[test]
+    fn san_round_trip() {
+        let game_state = get_game_state_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+        )
+        .unwrap();
+        for next_move in game_state.generate_legal_moves() {
+            let san = game_state.move_to_san(next_move);
+            assert_eq!(game_state.parse_san(&san), Ok(next_move), "{}", san);
+        }
+    }
+
+    #[test]
+    fn parse_san_variants() {
+        let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let castle_fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let scholars_mate = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let en_passant_fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        let promotion_fen = "3k4/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san_to_uci(castle_fen, "0-0"), Ok("e1g1".to_string()));
+        assert_eq!(san_to_uci(castle_fen, "O-O-O+"), Ok("e1c1".to_string()));
+        assert_eq!(san_to_uci(start_fen, "Nf3!?"), Ok("g1f3".to_string()));
+        assert_eq!(san_to_uci(start_fen, "Ng1-f3"), Ok("g1f3".to_string()));
+        assert_eq!(san_to_uci(start_fen, "e2-e4"), Ok("e2e4".to_string()));
+        assert_eq!(san_to_uci(scholars_mate, "Qf7"), Ok("h5f7".to_string()));
+        assert_eq!(san_to_uci(scholars_mate, "Qxf7#"), Ok("h5f7".to_string()));
+        assert_eq!(
+            san_to_uci(en_passant_fen, "exd6 e.p."),
+            Ok("e5d6".to_string())
+        );
+        assert_eq!(san_to_uci(en_passant_fen, "ed6"), Ok("e5d6".to_string()));
+        assert_eq!(san_to_uci(promotion_fen, "e8Q"), Ok("e7e8q".to_string()));
+        assert_eq!(san_to_uci(promotion_fen, "e8=r"), Ok("e7e8r".to_string()));
+    }
+
+    #[test]
+    fn parse_san_errors() {
+        let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        for text in ["", "N", "Nb", "Nbb1d2", "Nd9", "Zd2", "e8=K"] {
+            assert_eq!(
+                san_to_uci(fen, text),
+                Err(MoveParseError::InvalidFormat(text.to_string()))
+            );
+        }
+        assert_eq!(
+            san_to_uci(fen, "Nd2"),
+            Err(MoveParseError::AmbiguousMove("Nd2".to_string()))
+        );
+        assert_eq!(
+            san_to_uci(fen, "Qd2"),
+            Err(MoveParseError::IllegalMove("Qd2".to_string()))
+        );
+        assert_eq!(
+            san_to_uci(fen, "O-O"),
+            Err(MoveParseError::IllegalMove("O-O".to_string()))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 
 use super::{constants::*, fen_util::get_square_from_index, GameStateInfo};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -39,14 +39,14 @@ impl Piece {
     }
 }
 
-fn to_str<S>(x: &u64, s: S) -> Result<S::Ok, S::Error>
+pub(super) fn to_str<S>(x: &u64, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     s.serialize_str(&x.to_string())
 }
 
-fn from_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
+pub(super) fn from_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -144,6 +144,10 @@ impl Board {
         }
     }
 
+    pub fn get_occupied_bitmask(&self) -> u64 {
+        !self.empty
+    }
+
     pub fn is_index_empty(&self, index: usize) -> bool {
         let bit_mask: u64 = 1 << index;
         self.empty & bit_mask != 0
@@ -163,16 +167,7 @@ impl Board {
         self.is_pawn_attacking_index(index)
             || self.is_hop_piece_attack_index(index, KNIGHT_ATTACK_BITMASKS, self.knights)
             || self.is_hop_piece_attack_index(index, KING_ATTACK_BITMASKS, self.kings)
-            || self.is_slide_piece_attack_index(
-                CARDINAL_ATTACK_BITMASKS,
-                self.rooks | self.queens,
-                index,
-            )
-            || self.is_slide_piece_attack_index(
-                DIAGONAL_ATTACK_BITMASKS,
-                self.bishops | self.queens,
-                index,
-            )
+            || self.is_slide_piece_attack_index(index)
     }
 
     fn is_pawn_attacking_index(&self, index: usize) -> bool {
@@ -212,30 +207,15 @@ impl Board {
         attack_bitmask & opponent_bitmask != 0
     }
 
-    fn is_slide_piece_attack_index(
-        &self,
-        attack_bitmasks: [[u64; 4]; 64],
-        slide_piece_bitmask: u64,
-        index: usize,
-    ) -> bool {
+    fn is_slide_piece_attack_index(&self, index: usize) -> bool {
         let bit_mask: u64 = 1 << index;
         let color = self.get_square_color_mask(bit_mask);
         let opponent_color_bitmask = self.get_color_bitmask(color.opposite());
-        let opponent_cardinal_piece_bitmask = slide_piece_bitmask & opponent_color_bitmask;
-        let other_piece_bitmask = !self.empty ^ opponent_cardinal_piece_bitmask;
-        let attack_bitmasks = attack_bitmasks[index];
-        attack_bitmasks
-            .iter()
-            .enumerate()
-            .any(|(attack_bitmask_index, attack_bitmask)| {
-                let other_attack_bitmask = attack_bitmask & other_piece_bitmask;
-                let opponent_attack_bitmask = attack_bitmask & opponent_cardinal_piece_bitmask;
-                if attack_bitmask_index < 2 {
-                    opponent_attack_bitmask > other_attack_bitmask
-                } else {
-                    opponent_attack_bitmask.reverse_bits() > other_attack_bitmask.reverse_bits()
-                }
-            })
+        let occupied = !self.empty;
+        let cardinal_attackers = (self.rooks | self.queens) & opponent_color_bitmask;
+        let diagonal_attackers = (self.bishops | self.queens) & opponent_color_bitmask;
+        get_rook_attack_bitmask(index, occupied) & cardinal_attackers != 0
+            || get_bishop_attack_bitmask(index, occupied) & diagonal_attackers != 0
     }
 
     pub fn get_square(&self, index: usize) -> (Color, Piece) {
@@ -391,30 +371,22 @@ impl Board {
         self.assert_board_state(format!("update at index: {}", index));
     }
 
-    pub fn castle_black_queenside_open(&self, game_state_info: &GameStateInfo) -> bool {
-        let castle_square_open_bitmask = 0x00_00_00_00_00_00_00_0e;
-        let castle_square_attack_bitmask = 0x00_00_00_00_00_00_00_0c;
-        !self.empty & castle_square_open_bitmask == 0
-            && game_state_info.attack_mask & castle_square_attack_bitmask == 0
-    }
-
-    pub fn castle_black_kingside_open(&self, game_state_info: &GameStateInfo) -> bool {
-        let castle_square_bitmask = 0x00_00_00_00_00_00_00_60;
-        !self.empty & castle_square_bitmask == 0
-            && game_state_info.attack_mask & castle_square_bitmask == 0
-    }
-
-    pub fn castle_white_queenside_open(&self, game_state_info: &GameStateInfo) -> bool {
-        let castle_square_open_bitmask = 0x0e_00_00_00_00_00_00_00;
-        let castle_square_attack_bitmask = 0x0c_00_00_00_00_00_00_00;
-        !self.empty & castle_square_open_bitmask == 0
-            && game_state_info.attack_mask & castle_square_attack_bitmask == 0
-    }
-
-    pub fn castle_white_kingside_open(&self, game_state_info: &GameStateInfo) -> bool {
-        let castle_square_bitmask = 0x60_00_00_00_00_00_00_00;
-        !self.empty & castle_square_bitmask == 0
-            && game_state_info.attack_mask & castle_square_bitmask == 0
+    // The king and rook paths must be empty apart from the castling king and rook themselves, and
+    // the king may not pass through or land on an attacked square. Covers Chess960, where the king
+    // and rook can start anywhere on the back rank and may even land on each other's squares.
+    pub fn is_castle_open(
+        &self,
+        king_index: usize,
+        rook_index: usize,
+        king_to: usize,
+        rook_to: usize,
+        game_state_info: &GameStateInfo,
+    ) -> bool {
+        let path_bitmask =
+            get_rank_span_bitmask(king_index, king_to) | get_rank_span_bitmask(rook_index, rook_to);
+        let blockers = !self.empty & !(1 << king_index) & !(1 << rook_index);
+        blockers & path_bitmask == 0
+            && game_state_info.attack_mask & get_rank_span_bitmask(king_index, king_to) == 0
     }
 
     fn assert_board_state(&self, details: String) {
@@ -481,6 +453,12 @@ impl Board {
     }
 }
 
+// The squares from `from` to `to` inclusive, which must be on the same rank
+fn get_rank_span_bitmask(from: usize, to: usize) -> u64 {
+    let (low, high) = (from.min(to), from.max(to));
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
 pub struct Move {
     pub from: usize,
@@ -564,23 +542,137 @@ impl Move {
             ..Default::default()
         }
     }
+
+    // Long algebraic notation as used by UCI, e.g. e2e4, e1g1 for castling or e7e8q for promotion
+    pub fn to_uci(&self) -> String {
+        let mut uci = get_square_from_index(self.from) + &get_square_from_index(self.to);
+        if let Some(promotion_piece) = self.promotion_piece {
+            uci.push(match promotion_piece {
+                Piece::Knight => 'n',
+                Piece::Bishop => 'b',
+                Piece::Rook => 'r',
+                _ => 'q',
+            });
+        }
+        uci
+    }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum MoveParseError {
+    // The text is not of the form <from><to>[promotion], e.g. e2e4 or e7e8q
+    InvalidFormat(String),
+    // The text is well formed but does not match any legal move in the position
+    IllegalMove(String),
+    // The text matches more than one legal move, e.g. Nd7 when both knights can reach d7
+    AmbiguousMove(String),
+}
+
+impl Display for MoveParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveParseError::InvalidFormat(text) => write!(f, "invalid move format: {}", text),
+            MoveParseError::IllegalMove(text) => write!(f, "illegal move: {}", text),
+            MoveParseError::AmbiguousMove(text) => write!(f, "ambiguous move: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+// The file of the rook each side can still castle with, 0 for the a-file, so that any Chess960
+// start position can be represented
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct CastleAvailability {
-    pub white_kingside: bool,
-    pub white_queenside: bool,
-    pub black_kingside: bool,
-    pub black_queenside: bool,
+    pub white_kingside: Option<usize>,
+    pub white_queenside: Option<usize>,
+    pub black_kingside: Option<usize>,
+    pub black_queenside: Option<usize>,
 }
 
 impl Default for CastleAvailability {
     fn default() -> Self {
         CastleAvailability {
-            white_kingside: true,
-            white_queenside: true,
-            black_kingside: true,
-            black_queenside: true,
+            white_kingside: Some(7),
+            white_queenside: Some(0),
+            black_kingside: Some(7),
+            black_queenside: Some(0),
+        }
+    }
+}
+
+impl CastleAvailability {
+    pub fn none() -> CastleAvailability {
+        CastleAvailability {
+            white_kingside: None,
+            white_queenside: None,
+            black_kingside: None,
+            black_queenside: None,
+        }
+    }
+
+    pub fn get_mut(&mut self, color: Color, kingside: bool) -> &mut Option<usize> {
+        match (color, kingside) {
+            (Color::White, true) => &mut self.white_kingside,
+            (Color::White, false) => &mut self.white_queenside,
+            (Color::Black, true) => &mut self.black_kingside,
+            _ => &mut self.black_queenside,
         }
     }
+
+    // The square of the rook the side castles with, if it still can
+    pub fn get_rook_index(&self, color: Color, kingside: bool) -> Option<usize> {
+        let file = match (color, kingside) {
+            (Color::White, true) => self.white_kingside,
+            (Color::White, false) => self.white_queenside,
+            (Color::Black, true) => self.black_kingside,
+            _ => self.black_queenside,
+        }?;
+        Some(get_back_rank_start(color) + file)
+    }
+}
+
+// The index of the a-file square on the color's first rank
+pub fn get_back_rank_start(color: Color) -> usize {
+    if color == Color::White {
+        56
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod types_tests {
+    use super::*;
+
+    #[test]
+    fn move_to_uci() {
+        assert_eq!(Move::two_square_pawn_move(52, 36).to_uci(), "e2e4");
+        assert_eq!(Move::castle(60, 62).to_uci(), "e1g1");
+        assert_eq!(Move::promotion(12, 4, Piece::Queen).to_uci(), "e7e8q");
+        assert_eq!(
+            Move::promotion_capture(49, 56, Piece::Knight).to_uci(),
+            "b2a1n"
+        );
+    }
+
+    #[test]
+    fn rank_span_bitmask() {
+        assert_eq!(get_rank_span_bitmask(60, 62), 0x70_00_00_00_00_00_00_00);
+        assert_eq!(get_rank_span_bitmask(2, 0), 0x07);
+        assert_eq!(get_rank_span_bitmask(5, 5), 0x20);
+    }
+
+    #[test]
+    fn castle_rook_index() {
+        let castle = CastleAvailability {
+            white_kingside: Some(6),
+            black_queenside: None,
+            ..Default::default()
+        };
+        assert_eq!(castle.get_rook_index(Color::White, true), Some(62));
+        assert_eq!(castle.get_rook_index(Color::White, false), Some(56));
+        assert_eq!(castle.get_rook_index(Color::Black, true), Some(7));
+        assert_eq!(castle.get_rook_index(Color::Black, false), None);
+    }
 }
@@ -0,0 +1,334 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{fen_util::get_square_from_index, types::*, GameState};
+
+const WHITE_BACK_RANK: u64 = 0xff_00_00_00_00_00_00_00;
+const BLACK_BACK_RANK: u64 = 0x00_00_00_00_00_00_00_ff;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PositionProblem {
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    // Index of a pawn standing on the first or eighth rank
+    PawnOnBackRank(usize),
+    // A castling right whose king or rook is not on its starting square
+    CastlingWithoutKing { color: Color, kingside: bool },
+    CastlingWithoutRook { color: Color, kingside: bool },
+    // An en passant square that the last move could not have created
+    InvalidEnPassant(usize),
+    // The side that just moved left its own king in check
+    OpponentInCheck,
+}
+
+impl Display for PositionProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let color_name = |color: &Color| match color {
+            Color::White => "white",
+            _ => "black",
+        };
+        let side_name = |kingside: &bool| if *kingside { "kingside" } else { "queenside" };
+        match self {
+            PositionProblem::MissingKing(color) => write!(f, "{} has no king", color_name(color)),
+            PositionProblem::TooManyKings(color) => {
+                write!(f, "{} has more than one king", color_name(color))
+            }
+            PositionProblem::TooManyPawns(color) => {
+                write!(f, "{} has more than 8 pawns", color_name(color))
+            }
+            PositionProblem::TooManyPieces(color) => {
+                write!(f, "{} has more than 16 pieces", color_name(color))
+            }
+            PositionProblem::PawnOnBackRank(index) => {
+                write!(f, "pawn on back rank at {}", get_square_from_index(*index))
+            }
+            PositionProblem::CastlingWithoutKing { color, kingside } => write!(
+                f,
+                "{} can castle {} but the king is not on its starting square",
+                color_name(color),
+                side_name(kingside)
+            ),
+            PositionProblem::CastlingWithoutRook { color, kingside } => write!(
+                f,
+                "{} can castle {} but the rook is not on its starting square",
+                color_name(color),
+                side_name(kingside)
+            ),
+            PositionProblem::InvalidEnPassant(index) => {
+                let square = get_square_from_index(*index);
+                write!(f, "no pawn can have just moved past {}", square)
+            }
+            PositionProblem::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl GameState {
+    // Every reason the position could not arise in a legal game, as far as can be told without the
+    // move history. An empty list means the position is safe to search and play from.
+    pub fn validate(&self) -> Vec<PositionProblem> {
+        let board = &self.board;
+        let mut problems = Vec::new();
+        let kings = board.get_piece_bitmask(Piece::King);
+        let pawns = board.get_piece_bitmask(Piece::Pawn);
+        let mut kings_ok = true;
+        for color in [Color::White, Color::Black] {
+            let color_bitmask = board.get_color_bitmask(color);
+            match (kings & color_bitmask).count_ones() {
+                0 => problems.push(PositionProblem::MissingKing(color)),
+                1 => (),
+                _ => problems.push(PositionProblem::TooManyKings(color)),
+            }
+            kings_ok &= (kings & color_bitmask).count_ones() == 1;
+            if (pawns & color_bitmask).count_ones() > 8 {
+                problems.push(PositionProblem::TooManyPawns(color));
+            }
+            if color_bitmask.count_ones() > 16 {
+                problems.push(PositionProblem::TooManyPieces(color));
+            }
+        }
+
+        let mut back_rank_pawns = pawns & (WHITE_BACK_RANK | BLACK_BACK_RANK);
+        while back_rank_pawns != 0 {
+            let index = back_rank_pawns.trailing_zeros() as usize;
+            problems.push(PositionProblem::PawnOnBackRank(index));
+            back_rank_pawns &= back_rank_pawns - 1;
+        }
+
+        self.validate_castling(&mut problems);
+        self.validate_en_passant(&mut problems);
+
+        // Check detection needs exactly one king of each color
+        if kings_ok && self.is_opponent_in_check() {
+            problems.push(PositionProblem::OpponentInCheck);
+        }
+        problems
+    }
+
+    // The king must be on its back rank with the castling rook on the right side of it, and on the
+    // e-file unless the game is Chess960
+    fn validate_castling(&self, problems: &mut Vec<PositionProblem>) {
+        for (color, kingside) in [
+            (Color::White, true),
+            (Color::White, false),
+            (Color::Black, true),
+            (Color::Black, false),
+        ] {
+            let Some(rook_index) = self.castle.get_rook_index(color, kingside) else {
+                continue;
+            };
+            let back_rank_start = get_back_rank_start(color);
+            let rook_file = rook_index % 8;
+            let is_king_in_place = (0..8).any(|king_file| {
+                let is_start_file = if self.chess960 {
+                    if kingside {
+                        king_file < rook_file
+                    } else {
+                        king_file > rook_file
+                    }
+                } else {
+                    king_file == 4
+                };
+                is_start_file
+                    && self.board.get_square(back_rank_start + king_file) == (color, Piece::King)
+            });
+            if !is_king_in_place {
+                problems.push(PositionProblem::CastlingWithoutKing { color, kingside });
+            }
+            if self.board.get_square(rook_index) != (color, Piece::Rook) {
+                problems.push(PositionProblem::CastlingWithoutRook { color, kingside });
+            }
+        }
+    }
+
+    // The en passant square must be empty and on the sixth rank from the mover's side, with an
+    // opponent pawn just beyond it and an empty square behind it where that pawn started
+    fn validate_en_passant(&self, problems: &mut Vec<PositionProblem>) {
+        let Some(index) = self.en_passant_index else {
+            return;
+        };
+        let opponent = self.turn.opposite();
+        let (rank_start, pawn_index, start_index) = if self.turn == Color::White {
+            (16, index + 8, index.wrapping_sub(8))
+        } else {
+            (40, index.wrapping_sub(8), index + 8)
+        };
+        let is_valid = (rank_start..rank_start + 8).contains(&index)
+            && self.board.is_index_empty(index)
+            && self.board.is_index_empty(start_index)
+            && self.board.get_square(pawn_index) == (opponent, Piece::Pawn);
+        if !is_valid {
+            problems.push(PositionProblem::InvalidEnPassant(index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::super::fen_util::get_game_state_from_fen;
+    use super::*;
+
+    fn validate_fen(fen: &str) -> Vec<PositionProblem> {
+        get_game_state_from_fen(fen).unwrap().validate()
+    }
+
+    #[test]
+    fn legal_positions() {
+        assert_eq!(GameState::default().validate(), vec![]);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "rnbqkbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 3",
+            "8/8/8/8/8/8/8/k1K5 b - - 0 1",
+        ] {
+            assert_eq!(validate_fen(fen), vec![], "{}", fen);
+        }
+    }
+
+    #[test]
+    fn kings() {
+        assert_eq!(
+            validate_fen("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionProblem::MissingKing(Color::Black)]
+        );
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/K3K3 w - - 0 1"),
+            vec![PositionProblem::TooManyKings(Color::White)]
+        );
+        assert_eq!(
+            validate_fen("8/8/8/8/8/8/8/8 w - - 0 1"),
+            vec![
+                PositionProblem::MissingKing(Color::White),
+                PositionProblem::MissingKing(Color::Black)
+            ]
+        );
+    }
+
+    #[test]
+    fn material() {
+        assert_eq!(
+            validate_fen("4k3/8/8/8/P7/P7/PPPPPPPP/4K3 w - - 0 1"),
+            vec![PositionProblem::TooManyPawns(Color::White)]
+        );
+        assert_eq!(
+            validate_fen("rnbqkbnr/pppppppp/n7/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionProblem::TooManyPieces(Color::Black)]
+        );
+    }
+
+    #[test]
+    fn pawns_on_back_rank() {
+        assert_eq!(
+            validate_fen("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            vec![
+                PositionProblem::PawnOnBackRank(0),
+                PositionProblem::PawnOnBackRank(63)
+            ]
+        );
+    }
+
+    #[test]
+    fn castling_rights() {
+        assert_eq!(
+            validate_fen("r3k3/8/8/8/8/8/8/R4K1R w KQq - 0 1"),
+            vec![
+                PositionProblem::CastlingWithoutKing {
+                    color: Color::White,
+                    kingside: true
+                },
+                PositionProblem::CastlingWithoutKing {
+                    color: Color::White,
+                    kingside: false
+                },
+            ]
+        );
+        assert_eq!(validate_fen("4k2r/8/8/8/8/8/8/4K3 w k - 0 1"), vec![]);
+        assert_eq!(
+            validate_fen("4k1r1/8/8/8/8/8/8/4K3 w kq - 0 1"),
+            vec![
+                PositionProblem::CastlingWithoutRook {
+                    color: Color::Black,
+                    kingside: true
+                },
+                PositionProblem::CastlingWithoutRook {
+                    color: Color::Black,
+                    kingside: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn chess960_castling_rights() {
+        assert_eq!(
+            validate_fen("1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1"),
+            vec![]
+        );
+        assert_eq!(
+            validate_fen("1r2k1r1/8/8/8/8/8/8/1R1K2R1 w GBgc - 0 1"),
+            vec![PositionProblem::CastlingWithoutRook {
+                color: Color::Black,
+                kingside: false
+            }]
+        );
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/4K3/6R1 w G - 0 1"),
+            vec![PositionProblem::CastlingWithoutKing {
+                color: Color::White,
+                kingside: true
+            }]
+        );
+    }
+
+    #[test]
+    fn en_passant() {
+        // No black pawn on e5
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"),
+            vec![PositionProblem::InvalidEnPassant(20)]
+        );
+        // The target square is for the wrong side to move
+        assert_eq!(
+            validate_fen("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            vec![PositionProblem::InvalidEnPassant(44)]
+        );
+        // The pawn could not have passed through an occupied square
+        assert_eq!(
+            validate_fen("4k3/8/8/8/4P3/4N3/8/4K3 b - e3 0 1"),
+            vec![PositionProblem::InvalidEnPassant(44)]
+        );
+        assert_eq!(validate_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"), vec![]);
+    }
+
+    #[test]
+    fn opponent_in_check() {
+        assert_eq!(validate_fen("4k3/8/8/8/8/8/8/4KR2 w - - 0 1"), vec![]);
+        assert_eq!(
+            validate_fen("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionProblem::OpponentInCheck]
+        );
+        assert_eq!(
+            validate_fen("8/8/8/8/8/8/8/kK6 w - - 0 1"),
+            vec![PositionProblem::OpponentInCheck]
+        );
+    }
+
+    #[test]
+    fn problem_message() {
+        assert_eq!(
+            PositionProblem::PawnOnBackRank(63).to_string(),
+            "pawn on back rank at h1"
+        );
+        assert_eq!(
+            PositionProblem::CastlingWithoutRook {
+                color: Color::Black,
+                kingside: false
+            }
+            .to_string(),
+            "black can castle queenside but the rook is not on its starting square"
+        );
+    }
+}
@@ -1,11 +1,18 @@
-use rand::prelude::IndexedRandom;
-use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 
 use crate::board::{
     types::{Board, Color, Move, Piece},
     GameState,
 };
 
+use super::{
+    limits::{SearchLimits, Timer},
+    pawns::{get_passed_pawn_eval, get_pawn_structure, PawnStructure, PawnTable},
+    piece_square::get_piece_square_eval,
+    tapered::{get_game_phase, TaperedScore},
+    transposition::{Bound, TranspositionEntry, TranspositionTable},
+};
+
 // Values based on AlphaZero: https://arxiv.org/pdf/2009.04374.pdf (page 16)
 const PAWN_VALUE: i32 = 100;
 const KNIGHT_VALUE: i32 = 305;
@@ -19,7 +26,27 @@ const EVALED_PIECES: [Piece; 5] = [
     Piece::Rook,
     Piece::Queen,
 ];
-const MAX_PLY_DEPTH: u32 = 4;
+const PIECE_SQUARE_PIECES: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+// Iterative deepening stops here if no other limit is hit first
+pub const MAX_SEARCH_DEPTH: u32 = 64;
+const NODES_PER_TIME_CHECK: u64 = 1024;
+
+// Score for delivering checkmate on the current move. Mates further away score lower, so the
+// shortest mate is preferred. Anything above MATE_THRESHOLD is a forced mate.
+pub const MATE_SCORE: i32 = 1_000_000;
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+const INFINITY: i32 = MATE_SCORE + 1;
+// Captures that cannot bring the score within this margin of alpha are skipped in quiescence search.
+// Besides the captured material, a capture can change the positional terms, e.g. by removing a
+// passed pawn and creating another.
+const DELTA_MARGIN: i32 = 300;
 
 impl Piece {
     fn get_value(self: &Piece) -> i32 {
@@ -34,97 +61,450 @@ impl Piece {
     }
 }
 
-pub fn search(game_state: &GameState) -> Option<Move> {
-    search_at_depth(game_state, MAX_PLY_DEPTH)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // Relative to the side to move
+    pub score: i32,
+    // The depth of the last completed iteration
+    pub depth: u32,
+    pub nodes: u64,
+    pub time_ms: u64,
+    // The expected line of play, starting with the best move
+    pub pv: Vec<Move>,
 }
 
-pub fn search_at_depth(game_state: &GameState, depth: u32) -> Option<Move> {
-    inner_search(game_state, depth).map(|(m, _eval)| m)
+// Iterative deepening search: searches one ply deeper on each iteration until a limit is hit, and
+// returns the result of the last completed iteration. The first iteration always runs to
+// completion, so there is a best move whenever the side to move has a legal move.
+pub fn search(
+    game_state: &GameState,
+    limits: &SearchLimits,
+    table: &mut TranspositionTable,
+) -> SearchResult {
+    search_with_callback(game_state, limits, table, &AtomicBool::new(false), |_| {})
 }
 
-fn inner_search(game_state: &GameState, depth: u32) -> Option<(Move, i32)> {
-    if depth == 0 {
-        panic!("depth must be at least 1");
+// Same as search, but can be stopped early from another thread by setting `stop`, and reports the
+// result of every completed iteration to `on_iteration`
+pub fn search_with_callback(
+    game_state: &GameState,
+    limits: &SearchLimits,
+    table: &mut TranspositionTable,
+    stop: &AtomicBool,
+    mut on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let mut searcher = Searcher::new(limits, table, stop);
+    // Moves are made and unmade on this copy, so it is back to the root position between iterations
+    let mut root_state = game_state.clone();
+    let max_depth = limits.max_depth.unwrap_or(MAX_SEARCH_DEPTH).max(1);
+    let mut result = SearchResult {
+        best_move: None,
+        score: 0,
+        depth: 0,
+        nodes: 0,
+        time_ms: 0,
+        pv: vec![],
+    };
+    for depth in 1..=max_depth {
+        let (score, best_move) = searcher.negamax(&mut root_state, depth, 0, -INFINITY, INFINITY);
+        if searcher.stopped {
+            break;
+        }
+        result = SearchResult {
+            best_move,
+            score,
+            depth,
+            nodes: searcher.nodes,
+            time_ms: searcher.timer.elapsed_ms(),
+            pv: searcher.get_principal_variation(game_state, best_move, depth),
+        };
+        on_iteration(&result);
+        searcher.completed_depth = depth;
+        // A forced mate can't be improved on by searching deeper, and no legal moves means the
+        // game is over
+        if best_move.is_none() || score.abs() > MATE_THRESHOLD || searcher.should_not_deepen() {
+            break;
+        }
     }
-    let moves = game_state.generate_pseudo_legal_moves(true);
-    if moves.is_empty() {
-        return None;
+    result.nodes = searcher.nodes;
+    result.time_ms = searcher.timer.elapsed_ms();
+    result
+}
+
+struct Searcher<'a> {
+    table: &'a mut TranspositionTable,
+    pawn_table: PawnTable,
+    stop: &'a AtomicBool,
+    max_nodes: Option<u64>,
+    time_budget: Option<u64>,
+    timer: Timer,
+    nodes: u64,
+    completed_depth: u32,
+    stopped: bool,
+}
+
+impl<'a> Searcher<'a> {
+    fn new(
+        limits: &SearchLimits,
+        table: &'a mut TranspositionTable,
+        stop: &'a AtomicBool,
+    ) -> Searcher<'a> {
+        Searcher {
+            table,
+            pawn_table: PawnTable::default(),
+            stop,
+            max_nodes: limits.max_nodes,
+            time_budget: limits.time_budget(),
+            timer: Timer::start(),
+            nodes: 0,
+            completed_depth: 0,
+            stopped: false,
+        }
     }
-    let mut best_moves = vec![];
-    let mut best_side_eval = i32::MIN;
-    let sign: i32 = match game_state.turn {
-        Color::Black => -1,
-        _ => 1,
-    };
-    for m in moves {
-        let state = game_state.clone().perform_move(m);
-        let side_eval;
-        if depth > 1 {
-            let new_state = inner_search(&state, depth - 1);
-            if let Some((_, new_eval)) = new_state {
-                side_eval = sign * new_eval;
+
+    // Counts the node and checks whether a limit has been hit. Limits are only enforced once the
+    // first iteration is complete.
+    fn visit_node(&mut self) -> bool {
+        self.nodes += 1;
+        if self.completed_depth == 0 {
+            return false;
+        }
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.nodes >= max_nodes)
+        {
+            self.stopped = true;
+        }
+        // Reading the clock is relatively slow, so only check it every so often
+        if self.nodes.is_multiple_of(NODES_PER_TIME_CHECK)
+            && (self.stop.load(AtomicOrdering::Relaxed)
+                || self
+                    .time_budget
+                    .is_some_and(|time_budget| self.timer.elapsed_ms() >= time_budget))
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    // Follows the best moves stored in the transposition table from the root. Stored moves are
+    // checked for legality, since another position may have overwritten the entry.
+    fn get_principal_variation(
+        &self,
+        game_state: &GameState,
+        best_move: Option<Move>,
+        depth: u32,
+    ) -> Vec<Move> {
+        let mut pv = vec![];
+        let mut seen_hashes = vec![game_state.hash];
+        let mut state = game_state.clone();
+        let mut next_move = best_move;
+        while let Some(m) = next_move {
+            if pv.len() >= depth as usize || !state.generate_legal_moves().contains(&m) {
+                break;
+            }
+            pv.push(m);
+            state = state.perform_move(m);
+            if seen_hashes.contains(&state.hash) {
+                break;
+            }
+            seen_hashes.push(state.hash);
+            next_move = self
+                .table
+                .probe(state.hash)
+                .and_then(|entry| entry.best_move);
+        }
+        pv
+    }
+
+    // Each iteration takes several times longer than the previous one, so if half the time is
+    // already used, the next iteration would almost certainly not finish
+    fn should_not_deepen(&self) -> bool {
+        self.time_budget
+            .is_some_and(|time_budget| self.timer.elapsed_ms() >= time_budget / 2)
+    }
+
+    // Fail-soft alpha-beta search with principal variation search. The score is relative to the
+    // side to move.
+    fn negamax(
+        &mut self,
+        game_state: &mut GameState,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, Option<Move>) {
+        if self.visit_node() {
+            return (0, None);
+        }
+        let original_alpha = alpha;
+        let mut table_move = None;
+        if let Some(entry) = self.table.probe(game_state.hash) {
+            table_move = entry.best_move;
+            // Always search the root, so that there is a best move to return
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    return (score, entry.best_move);
+                }
+            }
+        }
+
+        if depth == 0 {
+            return (self.quiescence(game_state, ply, alpha, beta), None);
+        }
+        let mut moves = game_state.generate_legal_moves();
+        if moves.is_empty() {
+            return (get_no_move_eval(game_state, ply), None);
+        }
+        order_moves(game_state, &mut moves, table_move);
+
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for (index, &m) in moves.iter().enumerate() {
+            let undo_info = game_state.make_move(m);
+            let score = if index == 0 {
+                -self
+                    .negamax(game_state, depth - 1, ply + 1, -beta, -alpha)
+                    .0
             } else {
-                // If there is no best move, then it must be checkmate or stalemate
-                side_eval = if game_state.is_in_check() {
-                    -i32::MAX
+                // Assume the first move is best and try to prove it with a null window, only
+                // re-searching with the full window if this move turns out to be better
+                let score = -self
+                    .negamax(game_state, depth - 1, ply + 1, -alpha - 1, -alpha)
+                    .0;
+                if score > alpha && score < beta {
+                    -self
+                        .negamax(game_state, depth - 1, ply + 1, -beta, -alpha)
+                        .0
                 } else {
-                    0
-                };
+                    score
+                }
+            };
+            game_state.unmake_move(m, undo_info);
+            if self.stopped {
+                return (0, None);
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m);
+            }
+            if score > alpha {
+                alpha = score;
             }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(TranspositionEntry {
+            hash: game_state.hash,
+            depth,
+            bound,
+            score: score_to_table(best_score, ply),
+            best_move,
+        });
+        (best_score, best_move)
+    }
+
+    // Searches captures and promotions until the position is quiet, so that the static evaluation
+    // is never taken in the middle of an exchange
+    fn quiescence(
+        &mut self,
+        game_state: &mut GameState,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.visit_node() {
+            return 0;
+        }
+        // When in check, every evasion has to be searched since standing pat is not an option
+        let in_check = game_state.is_in_check();
+        let mut moves = if in_check {
+            game_state.generate_legal_moves()
         } else {
-            side_eval = sign * evaluate(&state);
+            game_state.generate_legal_captures()
+        };
+        // Without captures, the quiet moves still decide whether this is stalemate
+        if moves.is_empty() && (in_check || game_state.generate_legal_quiet_moves().is_empty()) {
+            return get_no_move_eval(game_state, ply);
         }
-        match side_eval.cmp(&best_side_eval) {
-            Ordering::Greater => {
-                best_moves = vec![m];
-                best_side_eval = side_eval;
+
+        let stand_pat = get_side_to_move_eval(game_state, &mut self.pawn_table);
+        let mut best_score = -INFINITY;
+        if !in_check {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+        }
+        order_moves(game_state, &mut moves, None);
+
+        for m in moves {
+            let optimistic_score = stand_pat + get_captured_value(game_state, m) + DELTA_MARGIN;
+            if !in_check && m.promotion_piece.is_none() && optimistic_score <= alpha {
+                // The skipped capture could still score up to this much, so a fail low has to
+                // report at least that to stay a valid upper bound
+                best_score = best_score.max(optimistic_score);
+                continue;
+            }
+            let undo_info = game_state.make_move(m);
+            let score = -self.quiescence(game_state, ply + 1, -beta, -alpha);
+            game_state.unmake_move(m, undo_info);
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
             }
-            Ordering::Equal => {
-                best_moves.push(m);
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
             }
-            Ordering::Less => {}
         }
+        best_score
     }
-    let best_move = best_moves.choose(&mut rand::rng());
-    best_move.map(|best_move| (*best_move, sign * best_side_eval))
 }
 
-fn evaluate(game_state: &GameState) -> i32 {
-    if game_state.generate_legal_states().is_empty() {
-        return get_no_move_eval(game_state);
+fn get_captured_value(game_state: &GameState, m: Move) -> i32 {
+    if m.en_passant {
+        // En passant targets an empty square, but always captures a pawn
+        PAWN_VALUE
+    } else if m.capture {
+        game_state.board.get_square(m.to).1.get_value()
+    } else {
+        0
     }
-    let board = &game_state.board;
+}
 
-    let mut white_eval = 0;
-    for piece in EVALED_PIECES {
-        white_eval += get_piece_eval(board, Color::White, piece);
+// Tries the move from the transposition table first, then captures ordered by most valuable
+// victim and least valuable attacker, then everything else
+fn order_moves(game_state: &GameState, moves: &mut [Move], table_move: Option<Move>) {
+    moves.sort_by_cached_key(|&m| {
+        if Some(m) == table_move {
+            return i32::MIN;
+        }
+        let mut key = 0;
+        if m.capture {
+            let (_, attacker) = game_state.board.get_square(m.from);
+            key -= 10 * get_captured_value(game_state, m) - attacker.get_value();
+        }
+        if let Some(promotion_piece) = m.promotion_piece {
+            key -= promotion_piece.get_value();
+        }
+        key
+    });
+}
+
+// Mate scores are stored relative to the position in the table instead of the root, since the
+// same position can be reached at different plies
+fn score_to_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
     }
+}
 
-    let mut black_eval = 0;
-    for piece in EVALED_PIECES {
-        black_eval += get_piece_eval(board, Color::Black, piece);
+fn score_from_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
     }
+}
 
-    white_eval - black_eval
+fn get_side_to_move_eval(game_state: &GameState, pawn_table: &mut PawnTable) -> i32 {
+    match game_state.turn {
+        Color::Black => -evaluate(game_state, pawn_table),
+        _ => evaluate(game_state, pawn_table),
+    }
+}
+
+// The static evaluation split into its parts, for debugging. All scores are from White's
+// perspective.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    // See tapered::get_game_phase
+    pub phase: i32,
+    pub middlegame: i32,
+    pub endgame: i32,
+    // The middlegame and endgame scores blended by the phase
+    pub score: i32,
+}
+
+pub fn get_evaluation(game_state: &GameState) -> Evaluation {
+    get_evaluation_with_pawns(game_state, &get_pawn_structure(&game_state.board))
+}
+
+// Same as get_evaluation, but with the pawn structure already worked out
+fn get_evaluation_with_pawns(game_state: &GameState, pawns: &PawnStructure) -> Evaluation {
+    let board = &game_state.board;
+    let eval = get_color_eval(board, Color::White) - get_color_eval(board, Color::Black)
+        + pawns.score
+        + get_passed_pawn_eval(board, pawns);
+    let phase = get_game_phase(board);
+    Evaluation {
+        phase,
+        middlegame: eval.middlegame,
+        endgame: eval.endgame,
+        score: eval.taper(phase),
+    }
+}
+
+// Static evaluation of the position from White's perspective
+fn evaluate(game_state: &GameState, pawn_table: &mut PawnTable) -> i32 {
+    get_evaluation_with_pawns(game_state, &pawn_table.get(&game_state.board)).score
+}
+
+fn get_color_eval(board: &Board, color: Color) -> TaperedScore {
+    let mut eval = TaperedScore::default();
+    for piece in EVALED_PIECES {
+        eval += get_piece_eval(board, color, piece);
+    }
+    for piece in PIECE_SQUARE_PIECES {
+        eval += get_piece_square_eval(board, color, piece);
+    }
+    eval
 }
 
 // Assumes there are no legal moves. Make sure to check that first!
-fn get_no_move_eval(game_state: &GameState) -> i32 {
+// The score is relative to the side to move, which has been checkmated if it is in check.
+fn get_no_move_eval(game_state: &GameState, ply: u32) -> i32 {
     if game_state.is_in_check() {
-        match game_state.turn {
-            Color::White => -i32::MAX,
-            Color::Black => i32::MAX,
-            _ => unreachable!(),
-        }
+        -MATE_SCORE + ply as i32
     } else {
         0
     }
 }
 
-fn get_piece_eval(board: &Board, color: Color, piece: Piece) -> i32 {
+// Material is worth the same throughout the game
+fn get_piece_eval(board: &Board, color: Color, piece: Piece) -> TaperedScore {
     let bits = board.get_color_bitmask(color) & board.get_piece_bitmask(piece);
     // We can never have more than 16 bits on, so this cast is safe
-    piece.get_value() * bits.count_ones() as i32
+    let value = piece.get_value() * bits.count_ones() as i32;
+    TaperedScore::new(value, value)
 }
 
 #[cfg(test)]
@@ -132,23 +512,353 @@ mod search_tests {
     use super::*;
     use crate::board::fen_util::*;
     use crate::board::GameState;
+    use crate::engine::tapered::MAX_PHASE;
 
     #[test]
     fn search_start_pos() {
-        let m = search_at_depth(&GameState::default(), 2);
+        let mut table = TranspositionTable::new(1);
+        let m = search(&GameState::default(), &SearchLimits::depth(2), &mut table).best_move;
         assert!(m.is_some());
     }
 
     #[test]
     fn search_capture() {
         let state =
-            get_game_state_from_fen("rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1");
-        let opt_m = search_at_depth(&state, 2);
+            get_game_state_from_fen("rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1")
+                .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let opt_m = search(&state, &SearchLimits::depth(2), &mut table).best_move;
         assert!(opt_m.is_some());
         let m = opt_m.unwrap();
         let expected = Move::capture(35, 28);
         assert_eq!(expected, m);
     }
+
+    #[test]
+    fn search_fills_transposition_table() {
+        let state = GameState::default();
+        let mut table = TranspositionTable::new(1);
+        let m = search(&state, &SearchLimits::depth(2), &mut table).best_move;
+        let entry = table.probe(state.hash).unwrap();
+        assert_eq!(entry.depth, 2);
+        assert_eq!(entry.best_move, m);
+        assert_eq!(
+            search(&state, &SearchLimits::depth(2), &mut table).best_move,
+            m
+        );
+    }
+
+    #[test]
+    fn search_mate_in_one() {
+        let state = get_game_state_from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = search(&state, &SearchLimits::depth(3), &mut table);
+        assert_eq!(result.best_move, Some(Move::new(56, 0)));
+        assert_eq!(result.score, MATE_SCORE - 1);
+        // No need to search deeper once the mate is found
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn search_does_not_hang_queen() {
+        // Qxd5 wins a pawn, but the queen is recaptured by cxd5
+        let state = get_game_state_from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let m = search(&state, &SearchLimits::depth(1), &mut table).best_move;
+        assert!(m.is_some());
+        assert_ne!(m, Some(Move::capture(51, 27)));
+
+        let state = state.perform_move(Move::capture(51, 27));
+        let score = full_window_quiescence(&state, 0);
+        let recaptured = state.perform_move(Move::capture(18, 27));
+        assert_eq!(score, -get_evaluation(&recaptured).score);
+        assert!(score > PAWN_VALUE / 2);
+    }
+
+    // Swaps the colors of all pieces and flips the board vertically
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| match c.is_ascii_uppercase() {
+                    true => c.to_ascii_lowercase(),
+                    false => c.to_ascii_uppercase(),
+                })
+                .collect()
+        };
+        let ranks: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort_by_key(|c| c.is_ascii_lowercase());
+        let en_passant = fields[3]
+            .replace('3', "x")
+            .replace('6', "3")
+            .replace('x', "6");
+        format!(
+            "{} {} {} {} 0 1",
+            ranks.join("/"),
+            turn,
+            castling.iter().collect::<String>(),
+            en_passant
+        )
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        assert_eq!(get_evaluation(&GameState::default()).score, 0);
+        let symmetric = get_game_state_from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4",
+        )
+        .unwrap();
+        assert_eq!(get_evaluation(&symmetric).score, 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq c6 0 4",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "6k1/5pp1/7p/8/2B5/8/5PPP/3R2K1 b - - 0 1",
+            "4k3/8/8/3q4/8/8/2N5/4K3 w - - 0 1",
+        ] {
+            let game_state = get_game_state_from_fen(fen).unwrap();
+            let mirrored = get_game_state_from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(
+                get_evaluation(&game_state).score,
+                -get_evaluation(&mirrored).score,
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn evaluation_prefers_development() {
+        let game_state = GameState::default();
+        let knight_to_centre = game_state.perform_move(Move::new(62, 45));
+        let knight_to_rim = game_state.perform_move(Move::new(62, 47));
+        assert!(get_evaluation(&knight_to_centre).score > get_evaluation(&knight_to_rim).score);
+        assert!(get_evaluation(&knight_to_centre).score > 0);
+    }
+
+    #[test]
+    fn evaluation_centralises_king_in_endgame() {
+        let corner = get_game_state_from_fen("7k/8/8/8/8/8/4P3/K7 w - - 0 1").unwrap();
+        let centre = get_game_state_from_fen("7k/8/8/8/3K4/8/4P3/8 w - - 0 1").unwrap();
+        assert!(get_evaluation(&centre).score > get_evaluation(&corner).score);
+        let castled =
+            get_game_state_from_fen("rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w - - 0 1")
+                .unwrap();
+        let centre =
+            get_game_state_from_fen("rnbq1rk1/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1R2 w - - 0 1")
+                .unwrap();
+        assert!(get_evaluation(&castled).score > get_evaluation(&centre).score);
+    }
+
+    #[test]
+    fn evaluation_phases() {
+        let evaluation = get_evaluation(&GameState::default());
+        assert_eq!(evaluation.phase, MAX_PHASE);
+        assert_eq!(evaluation.score, evaluation.middlegame);
+
+        let pawn_endgame = get_game_state_from_fen("4k3/8/8/8/3K4/8/4P3/8 w - - 0 1").unwrap();
+        let evaluation = get_evaluation(&pawn_endgame);
+        assert_eq!(evaluation.phase, 0);
+        assert_eq!(evaluation.score, evaluation.endgame);
+        assert!(evaluation.endgame > evaluation.middlegame);
+
+        // The king walking to the centre is only good as the pieces come off
+        let king_walk = get_game_state_from_fen("r2q1rk1/8/8/8/8/4K3/8/R2Q1R2 w - - 0 1").unwrap();
+        let evaluation = get_evaluation(&king_walk);
+        assert_eq!(evaluation.phase, 16);
+        assert!(evaluation.middlegame < 0);
+        assert!(evaluation.endgame > 0);
+        assert!(evaluation.middlegame < evaluation.score && evaluation.score < evaluation.endgame);
+    }
+
+    #[test]
+    fn quiescence_stand_pat() {
+        // Black can capture the knight, but should not since the pawn recaptures
+        let state = get_game_state_from_fen("4k3/8/4r3/8/4N3/3P4/8/4K3 b - - 0 1").unwrap();
+        let score = full_window_quiescence(&state, 0);
+        assert_eq!(
+            score,
+            get_side_to_move_eval(&state, &mut PawnTable::default())
+        );
+    }
+
+    #[test]
+    fn quiescence_fail_low_is_upper_bound() {
+        // White is too far below alpha for exd5 to matter, but nothing can recapture
+        let state = get_game_state_from_fen("4k3/q7/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut searcher = Searcher::new(&SearchLimits::default(), &mut table, &stop);
+        let alpha = -300;
+        let score = searcher.quiescence(&mut state.clone(), 0, alpha, alpha + 100);
+        assert!(score <= alpha);
+        assert!(score >= full_window_quiescence(&state, 0));
+    }
+
+    #[test]
+    fn search_depth_limit() {
+        let mut table = TranspositionTable::new(1);
+        let result = search(&GameState::default(), &SearchLimits::depth(3), &mut table);
+        assert!(result.best_move.is_some());
+        assert_eq!(result.depth, 3);
+    }
+
+    #[test]
+    fn search_node_limit() {
+        let state = get_game_state_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+        )
+        .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = search(&state, &SearchLimits::nodes(5_000), &mut table);
+        assert!(result.best_move.is_some());
+        assert!(result.depth >= 1);
+        assert!(result.depth < MAX_SEARCH_DEPTH);
+    }
+
+    #[test]
+    fn search_time_limit() {
+        let state = get_game_state_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+        )
+        .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let timer = Timer::start();
+        let result = search(&state, &SearchLimits::move_time(50), &mut table);
+        assert!(result.best_move.is_some());
+        // Generous bound, since the first iteration always completes
+        assert!(timer.elapsed_ms() < 5_000);
+    }
+
+    #[test]
+    fn search_stop_flag() {
+        let mut table = TranspositionTable::new(1);
+        let stop = AtomicBool::new(true);
+        let mut depths = vec![];
+        let result = search_with_callback(
+            &GameState::default(),
+            &SearchLimits::default(),
+            &mut table,
+            &stop,
+            |iteration| depths.push(iteration.depth),
+        );
+        // The first iteration always completes, but nothing after that
+        assert!(result.best_move.is_some());
+        assert!(result.depth < MAX_SEARCH_DEPTH);
+        assert_eq!(depths, (1..=result.depth).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn search_principal_variation() {
+        let mut table = TranspositionTable::new(1);
+        let state = GameState::default();
+        let result = search(&state, &SearchLimits::depth(4), &mut table);
+        assert_eq!(result.pv.len(), 4);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        let mut state = state;
+        for m in result.pv {
+            assert!(state.generate_legal_moves().contains(&m));
+            state = state.perform_move(m);
+        }
+    }
+
+    #[test]
+    fn search_no_legal_moves() {
+        let state = get_game_state_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = search(&state, &SearchLimits::move_time(50), &mut table);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+
+    fn full_window_quiescence(game_state: &GameState, ply: u32) -> i32 {
+        let mut table = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut searcher = Searcher::new(&SearchLimits::default(), &mut table, &stop);
+        searcher.quiescence(&mut game_state.clone(), ply, -INFINITY, INFINITY)
+    }
+
+    // Plain negamax without pruning or the transposition table, to check the search against.
+    // Leaves use the same quiescence search, but with a full window.
+    fn minimax(game_state: &GameState, depth: u32, ply: u32) -> i32 {
+        if depth == 0 {
+            return full_window_quiescence(game_state, ply);
+        }
+        let moves = game_state.generate_legal_moves();
+        if moves.is_empty() {
+            return get_no_move_eval(game_state, ply);
+        }
+        moves
+            .iter()
+            .map(|&m| -minimax(&game_state.perform_move(m), depth - 1, ply + 1))
+            .max()
+            .unwrap()
+    }
+
+    fn assert_same_score_as_minimax(fen: &str, max_depth: u32) {
+        let mut state = get_game_state_from_fen(fen).unwrap();
+        for depth in 1..=max_depth {
+            let mut table = TranspositionTable::new(1);
+            let stop = AtomicBool::new(false);
+            let mut searcher = Searcher::new(&SearchLimits::default(), &mut table, &stop);
+            let (score, _) = searcher.negamax(&mut state, depth, 0, -INFINITY, INFINITY);
+            assert_eq!(score, minimax(&state, depth, 0), "depth {}", depth);
+        }
+    }
+
+    // Positions from the perft tests in board::perft_tests. The slower ones are ignored by
+    // default.
+
+    #[test]
+    fn alpha_beta_matches_minimax_pos_1() {
+        assert_same_score_as_minimax(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            3,
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn alpha_beta_matches_minimax_pos_2() {
+        assert_same_score_as_minimax(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+            3,
+        );
+    }
+
+    #[test]
+    fn alpha_beta_matches_minimax_pos_3() {
+        assert_same_score_as_minimax("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -", 3);
+    }
+
+    #[test]
+    #[ignore]
+    fn alpha_beta_matches_minimax_pos_4() {
+        assert_same_score_as_minimax(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn alpha_beta_matches_minimax_pos_5() {
+        assert_same_score_as_minimax(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            3,
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn alpha_beta_matches_minimax_pos_6() {
+        assert_same_score_as_minimax(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            3,
+        );
+    }
 }
 
 #[cfg(test)]
@@ -156,14 +866,19 @@ mod benchmark_tests {
     extern crate test;
 
     use crate::board::GameState;
+    use crate::engine::{limits::SearchLimits, transposition::TranspositionTable};
 
-    use super::search_at_depth;
+    use super::search;
     use test::Bencher;
 
     #[bench]
     fn search_depth_1_start_position(b: &mut Bencher) {
         let game_state = GameState::default();
-        b.iter(|| search_at_depth(&game_state, 1));
+        let mut table = TranspositionTable::default();
+        b.iter(|| {
+            table.clear();
+            search(&game_state, &SearchLimits::depth(1), &mut table).best_move
+        });
     }
 
     // These tests are slow, so ignore them by default
@@ -172,20 +887,32 @@ mod benchmark_tests {
     #[ignore]
     fn search_depth_2_start_position(b: &mut Bencher) {
         let game_state = GameState::default();
-        b.iter(|| search_at_depth(&game_state, 2));
+        let mut table = TranspositionTable::default();
+        b.iter(|| {
+            table.clear();
+            search(&game_state, &SearchLimits::depth(2), &mut table).best_move
+        });
     }
 
     #[bench]
     #[ignore]
     fn search_depth_4_start_position(b: &mut Bencher) {
         let game_state = GameState::default();
-        b.iter(|| search_at_depth(&game_state, 4));
+        let mut table = TranspositionTable::default();
+        b.iter(|| {
+            table.clear();
+            search(&game_state, &SearchLimits::depth(4), &mut table).best_move
+        });
     }
 
     #[bench]
     #[ignore]
     fn search_depth_5_start_position(b: &mut Bencher) {
         let game_state = GameState::default();
-        b.iter(|| search_at_depth(&game_state, 5));
+        let mut table = TranspositionTable::default();
+        b.iter(|| {
+            table.clear();
+            search(&game_state, &SearchLimits::depth(5), &mut table).best_move
+        });
     }
 }
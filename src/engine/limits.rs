@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+// Fraction of the remaining clock time to spend on a single move
+const MOVES_TO_GO: u64 = 30;
+// Never let the clock run below this many milliseconds, to leave room for communication delays
+const CLOCK_SAFETY_MARGIN: u64 = 50;
+
+// Constraints on how long a search may run. A search with no limits runs until the maximum search
+// depth is reached or it is stopped externally.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub max_depth: Option<u32>,
+    pub max_nodes: Option<u64>,
+    // Time to spend on this move in milliseconds
+    pub move_time: Option<u64>,
+    pub clock: Option<Clock>,
+}
+
+// The time left on the clock of the side to move, in milliseconds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clock {
+    pub remaining: u64,
+    pub increment: u64,
+}
+
+impl SearchLimits {
+    pub fn depth(max_depth: u32) -> SearchLimits {
+        SearchLimits {
+            max_depth: Some(max_depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(max_nodes: u64) -> SearchLimits {
+        SearchLimits {
+            max_nodes: Some(max_nodes),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: u64) -> SearchLimits {
+        SearchLimits {
+            move_time: Some(move_time),
+            ..Default::default()
+        }
+    }
+
+    pub fn clock(remaining: u64, increment: u64) -> SearchLimits {
+        SearchLimits {
+            clock: Some(Clock {
+                remaining,
+                increment,
+            }),
+            ..Default::default()
+        }
+    }
+
+    // The number of milliseconds the search may use, if it is limited by time at all
+    pub fn time_budget(&self) -> Option<u64> {
+        let clock_budget = self.clock.map(|clock| {
+            let available = clock.remaining.saturating_sub(CLOCK_SAFETY_MARGIN);
+            (clock.remaining / MOVES_TO_GO + clock.increment * 3 / 4).min(available)
+        });
+        match (self.move_time, clock_budget) {
+            (Some(move_time), Some(clock_budget)) => Some(move_time.min(clock_budget)),
+            (move_time, clock_budget) => move_time.or(clock_budget),
+        }
+    }
+}
+
+// Measures elapsed wall clock time. std::time::Instant is not available in the browser, so use the
+// JavaScript clock there instead when it can be reached through the wasm feature.
+pub struct Timer {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    start: f64,
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    start: std::time::Instant,
+}
+
+impl Timer {
+    pub fn start() -> Timer {
+        Timer {
+            #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+            start: web_sys::js_sys::Date::now(),
+            #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+        {
+            (web_sys::js_sys::Date::now() - self.start) as u64
+        }
+        #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+        {
+            self.start.elapsed().as_millis() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::*;
+
+    #[test]
+    fn no_time_budget() {
+        assert_eq!(SearchLimits::default().time_budget(), None);
+        assert_eq!(SearchLimits::depth(5).time_budget(), None);
+        assert_eq!(SearchLimits::nodes(1000).time_budget(), None);
+    }
+
+    #[test]
+    fn move_time_budget() {
+        assert_eq!(SearchLimits::move_time(500).time_budget(), Some(500));
+    }
+
+    #[test]
+    fn clock_budget() {
+        assert_eq!(SearchLimits::clock(60_000, 0).time_budget(), Some(2_000));
+        assert_eq!(
+            SearchLimits::clock(60_000, 1_000).time_budget(),
+            Some(2_750)
+        );
+    }
+
+    #[test]
+    fn clock_budget_keeps_safety_margin() {
+        assert_eq!(SearchLimits::clock(100, 1_000).time_budget(), Some(50));
+        assert_eq!(SearchLimits::clock(20, 0).time_budget(), Some(0));
+    }
+
+    #[test]
+    fn move_time_and_clock_budget() {
+        let limits = SearchLimits {
+            move_time: Some(1_000),
+            ..SearchLimits::clock(60_000, 0)
+        };
+        assert_eq!(limits.time_budget(), Some(1_000));
+    }
+}
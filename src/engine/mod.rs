@@ -1 +1,6 @@
 pub mod generate;
+pub mod limits;
+pub mod pawns;
+pub mod piece_square;
+pub mod tapered;
+pub mod transposition;
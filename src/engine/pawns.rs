@@ -0,0 +1,314 @@
+use crate::board::types::{Board, Color, Piece};
+
+use super::tapered::TaperedScore;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+const RANK_8: u64 = 0xff;
+
+// Per extra pawn on a file
+const DOUBLED_PAWN: TaperedScore = TaperedScore::new(-10, -25);
+// No friendly pawns on the neighbouring files
+const ISOLATED_PAWN: TaperedScore = TaperedScore::new(-12, -15);
+// Every friendly pawn on the neighbouring files has moved past it, and it cannot advance without
+// being captured by a pawn
+const BACKWARD_PAWN: TaperedScore = TaperedScore::new(-8, -12);
+// Defended by a pawn or standing next to one. Indexed by rank counted from the pawn's own side,
+// from 0 for its back rank to 7 for the promotion rank.
+const CONNECTED_PAWN: [TaperedScore; 8] = [
+    TaperedScore::new(0, 0),
+    TaperedScore::new(3, 1),
+    TaperedScore::new(5, 2),
+    TaperedScore::new(7, 4),
+    TaperedScore::new(10, 8),
+    TaperedScore::new(18, 15),
+    TaperedScore::new(30, 30),
+    TaperedScore::new(0, 0),
+];
+// No enemy pawns in front of it on its own or the neighbouring files, indexed the same way. Halved
+// when a piece stands in the way.
+const PASSED_PAWN: [TaperedScore; 8] = [
+    TaperedScore::new(0, 0),
+    TaperedScore::new(5, 10),
+    TaperedScore::new(8, 15),
+    TaperedScore::new(15, 30),
+    TaperedScore::new(30, 55),
+    TaperedScore::new(55, 90),
+    TaperedScore::new(90, 140),
+    TaperedScore::new(0, 0),
+];
+
+// Enough for the pawn structures that come up in one search, which are far fewer than positions
+const PAWN_TABLE_SIZE: usize = 1 << 13;
+
+// The evaluation terms that only depend on where the pawns are
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnStructure {
+    // From White's perspective, without the passed pawn bonuses
+    pub score: TaperedScore,
+    pub white_passed: u64,
+    pub black_passed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PawnEntry {
+    white_pawns: u64,
+    black_pawns: u64,
+    structure: PawnStructure,
+}
+
+// Fixed-size cache of pawn structures, indexed by the pawn bitboards. Pawns move far less often
+// than other pieces, so most positions in a search share their pawn structure with an earlier one.
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        PawnTable {
+            entries: vec![None; PAWN_TABLE_SIZE],
+        }
+    }
+}
+
+impl PawnTable {
+    pub fn get(&mut self, board: &Board) -> PawnStructure {
+        let (white_pawns, black_pawns) = get_pawn_bitmasks(board);
+        let index = get_index(white_pawns, black_pawns);
+        match self.entries[index] {
+            Some(entry) if entry.white_pawns == white_pawns && entry.black_pawns == black_pawns => {
+                entry.structure
+            }
+            _ => {
+                let structure = get_pawn_structure(board);
+                self.entries[index] = Some(PawnEntry {
+                    white_pawns,
+                    black_pawns,
+                    structure,
+                });
+                structure
+            }
+        }
+    }
+}
+
+fn get_index(white_pawns: u64, black_pawns: u64) -> usize {
+    // Mixes all the bits into the top ones
+    let hash = white_pawns.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ black_pawns
+            .wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            .rotate_left(32);
+    (hash >> (64 - PAWN_TABLE_SIZE.trailing_zeros())) as usize
+}
+
+fn get_pawn_bitmasks(board: &Board) -> (u64, u64) {
+    let pawns = board.get_piece_bitmask(Piece::Pawn);
+    (
+        pawns & board.get_color_bitmask(Color::White),
+        pawns & board.get_color_bitmask(Color::Black),
+    )
+}
+
+pub fn get_pawn_structure(board: &Board) -> PawnStructure {
+    let (white_pawns, black_pawns) = get_pawn_bitmasks(board);
+    let (white_score, white_passed) =
+        get_color_pawn_structure(white_pawns, black_pawns, Color::White);
+    let (black_score, black_passed) =
+        get_color_pawn_structure(black_pawns, white_pawns, Color::Black);
+    PawnStructure {
+        score: white_score - black_score,
+        white_passed,
+        black_passed,
+    }
+}
+
+// The passed pawn bonuses from White's perspective. Whether a pawn is blocked depends on the other
+// pieces, so this is not part of the cached pawn structure.
+pub fn get_passed_pawn_eval(board: &Board, structure: &PawnStructure) -> TaperedScore {
+    let occupied = board.get_occupied_bitmask();
+    let get_eval = |mut passed: u64, color: Color| {
+        let mut eval = TaperedScore::default();
+        while passed != 0 {
+            let index = passed.trailing_zeros() as usize;
+            let bonus = PASSED_PAWN[get_relative_rank(index, color)];
+            let path = FILE_A << (index % 8) & get_ranks_ahead_mask(index / 8, color);
+            eval += if path & occupied == 0 {
+                bonus
+            } else {
+                TaperedScore::new(bonus.middlegame / 2, bonus.endgame / 2)
+            };
+            passed &= passed - 1;
+        }
+        eval
+    };
+    get_eval(structure.white_passed, Color::White) - get_eval(structure.black_passed, Color::Black)
+}
+
+// The score of one side's pawns and which of them are passed
+fn get_color_pawn_structure(own: u64, enemy: u64, color: Color) -> (TaperedScore, u64) {
+    let mut score = TaperedScore::default();
+    for file in 0..8 {
+        let count = (own & FILE_A << file).count_ones() as i32;
+        if count > 1 {
+            score += DOUBLED_PAWN * (count - 1);
+        }
+    }
+
+    let own_attacks = get_pawn_attacks(own, color);
+    let enemy_attacks = get_pawn_attacks(enemy, color.opposite());
+    let mut passed = 0;
+    let mut pawns = own;
+    while pawns != 0 {
+        let index = pawns.trailing_zeros() as usize;
+        let bit = 1 << index;
+        let (row, file) = (index / 8, index % 8);
+        let file_mask = FILE_A << file;
+        let adjacent_files = ((file_mask & !FILE_A) >> 1) | ((file_mask & !FILE_H) << 1);
+        let ahead = get_ranks_ahead_mask(row, color);
+        let stop_square = match color {
+            Color::White => bit >> 8,
+            _ => bit << 8,
+        };
+
+        if own & adjacent_files == 0 {
+            score += ISOLATED_PAWN;
+        } else if own & adjacent_files & !ahead == 0 && enemy_attacks & stop_square != 0 {
+            score += BACKWARD_PAWN;
+        }
+        if own_attacks & bit != 0 || own & adjacent_files & RANK_8 << (row * 8) != 0 {
+            score += CONNECTED_PAWN[get_relative_rank(index, color)];
+        }
+        if enemy & (file_mask | adjacent_files) & ahead == 0 && own & file_mask & ahead == 0 {
+            passed |= bit;
+        }
+        pawns &= pawns - 1;
+    }
+    (score, passed)
+}
+
+// Squares attacked by the given pawns. White pawns move towards index 0.
+fn get_pawn_attacks(pawns: u64, color: Color) -> u64 {
+    match color {
+        Color::White => ((pawns & !FILE_A) >> 9) | ((pawns & !FILE_H) >> 7),
+        _ => ((pawns & !FILE_A) << 7) | ((pawns & !FILE_H) << 9),
+    }
+}
+
+// Every rank in front of the given row, which counts from 0 for the 8th rank, in the direction the
+// color's pawns move
+fn get_ranks_ahead_mask(row: usize, color: Color) -> u64 {
+    match color {
+        Color::White => (1 << (row * 8)) - 1,
+        // Shifting in two steps keeps the shift below 64 for the 1st rank
+        _ => !0 << (row * 8) << 8,
+    }
+}
+
+// From 0 for the color's own back rank to 7 for the promotion rank
+fn get_relative_rank(index: usize, color: Color) -> usize {
+    match color {
+        Color::White => 7 - index / 8,
+        _ => index / 8,
+    }
+}
+
+#[cfg(test)]
+mod pawn_tests {
+    use super::*;
+    use crate::board::fen_util::get_game_state_from_fen;
+    use crate::board::GameState;
+
+    fn get_structure(fen: &str) -> PawnStructure {
+        get_pawn_structure(&get_game_state_from_fen(fen).unwrap().board)
+    }
+
+    #[test]
+    fn start_position() {
+        let structure = get_pawn_structure(&GameState::default().board);
+        assert_eq!(structure.score, TaperedScore::default());
+        assert_eq!(structure.white_passed, 0);
+        assert_eq!(structure.black_passed, 0);
+    }
+
+    #[test]
+    fn doubled_and_isolated() {
+        // Doubled and isolated pawns on the a-file against a connected pair
+        let structure = get_structure("4k3/8/8/8/8/P7/P5pp/4K3 w - - 0 1");
+        let white = DOUBLED_PAWN + ISOLATED_PAWN * 2;
+        let black = CONNECTED_PAWN[6] * 2;
+        assert_eq!(structure.score, white - black);
+    }
+
+    #[test]
+    fn backward() {
+        // The e4 pawn has moved past d3, and c5 covers d4
+        let structure = get_structure("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1");
+        let white = BACKWARD_PAWN + CONNECTED_PAWN[3];
+        let black = ISOLATED_PAWN;
+        assert_eq!(structure.score, white - black);
+    }
+
+    #[test]
+    fn connected() {
+        // A phalanx on the 4th rank and a pawn chain behind it
+        let structure = get_structure("4k3/8/8/8/3PP3/2P5/8/4K3 w - - 0 1");
+        // c3 only defends d4, it is not connected itself
+        assert_eq!(structure.score, CONNECTED_PAWN[3] * 2);
+    }
+
+    #[test]
+    fn passed() {
+        let structure = get_structure("4k3/8/1p6/8/P2P4/8/5p2/4K3 w - - 0 1");
+        // The a4 pawn is stopped by b6, the d4 pawn is free
+        assert_eq!(structure.white_passed, 1 << 35);
+        assert_eq!(structure.black_passed, 1 << 53);
+        // A pawn behind its own pawn on the same file is not passed
+        let structure = get_structure("4k3/8/8/3P4/3P4/8/8/4K3 w - - 0 1");
+        assert_eq!(structure.white_passed, 1 << 27);
+    }
+
+    #[test]
+    fn blocked_passed_pawn() {
+        let free = get_game_state_from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = get_game_state_from_fen("4k3/3n4/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let eval = |game_state: &GameState| {
+            get_passed_pawn_eval(&game_state.board, &get_pawn_structure(&game_state.board))
+        };
+        assert_eq!(eval(&free), PASSED_PAWN[4]);
+        assert_eq!(eval(&blocked), TaperedScore::new(15, 27));
+    }
+
+    #[test]
+    fn passed_pawn_bonus_grows_with_rank() {
+        for rank in 1..6 {
+            assert!(PASSED_PAWN[rank + 1].middlegame > PASSED_PAWN[rank].middlegame);
+            assert!(PASSED_PAWN[rank + 1].endgame > PASSED_PAWN[rank].endgame);
+        }
+    }
+
+    #[test]
+    fn color_symmetry() {
+        let structure = get_structure("4k3/pp3p2/2p3p1/3P4/8/1P6/P4PPP/4K3 w - - 0 1");
+        let mirrored = get_structure("4k3/p4ppp/1p6/8/3p4/2P3P1/PP3P2/4K3 w - - 0 1");
+        assert_eq!(structure.score, -mirrored.score);
+        assert_eq!(structure.white_passed.swap_bytes(), mirrored.black_passed);
+        assert_eq!(structure.black_passed.swap_bytes(), mirrored.white_passed);
+    }
+
+    #[test]
+    fn table_caches_structure() {
+        let mut table = PawnTable::default();
+        let game_state = get_game_state_from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let structure = table.get(&game_state.board);
+        assert_eq!(structure, get_pawn_structure(&game_state.board));
+        // A knight move leaves the pawns, and so the cached entry, as they were
+        let moved = game_state.perform_move(game_state.parse_san("Nd3").unwrap());
+        let (white_pawns, black_pawns) = get_pawn_bitmasks(&moved.board);
+        let entry = table.entries[get_index(white_pawns, black_pawns)].unwrap();
+        assert_eq!(entry.structure, structure);
+        assert_eq!(table.get(&moved.board), structure);
+    }
+}
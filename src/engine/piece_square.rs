@@ -0,0 +1,212 @@
+use crate::board::types::{Board, Color, Piece};
+
+use super::tapered::TaperedScore;
+
+// Bonuses for where each piece stands, from White's point of view with a8 first, the same order
+// as the board indices. Black uses the same tables mirrored vertically.
+// The middlegame tables are from the Simplified Evaluation Function:
+// https://www.chessprogramming.org/Simplified_Evaluation_Function
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+    5, 5, 10, 25, 25, 10, 5, 5,
+    0, 0, 0, 20, 20, 0, 0, 0,
+    5, -5, -10, 0, 0, -10, -5, 5,
+    5, 10, 10, -20, -20, 10, 10, 5,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+// Passed pawns decide most endgames, so every step forward counts
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    5, 5, 5, 5, 5, 5, 5, 5,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+#[rustfmt::skip]
+const KNIGHT_MIDDLEGAME: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20, 0, 0, 0, 0, -20, -40,
+    -30, 0, 10, 15, 15, 10, 0, -30,
+    -30, 5, 15, 20, 20, 15, 5, -30,
+    -30, 0, 15, 20, 20, 15, 0, -30,
+    -30, 5, 10, 15, 15, 10, 5, -30,
+    -40, -20, 0, 5, 5, 0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const KNIGHT_ENDGAME: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20, 0, 0, 0, 0, -20, -40,
+    -30, 0, 10, 15, 15, 10, 0, -30,
+    -30, 0, 15, 20, 20, 15, 0, -30,
+    -30, 0, 15, 20, 20, 15, 0, -30,
+    -30, 0, 10, 15, 15, 10, 0, -30,
+    -40, -20, 0, 0, 0, 0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP_MIDDLEGAME: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10,
+    -10, 5, 5, 10, 10, 5, 5, -10,
+    -10, 0, 10, 10, 10, 10, 0, -10,
+    -10, 10, 10, 10, 10, 10, 10, -10,
+    -10, 5, 0, 0, 0, 0, 5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const BISHOP_ENDGAME: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10,
+    -10, 0, 10, 15, 15, 10, 0, -10,
+    -10, 0, 10, 15, 15, 10, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK_MIDDLEGAME: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    5, 10, 10, 10, 10, 10, 10, 5,
+    -5, 0, 0, 0, 0, 0, 0, -5,
+    -5, 0, 0, 0, 0, 0, 0, -5,
+    -5, 0, 0, 0, 0, 0, 0, -5,
+    -5, 0, 0, 0, 0, 0, 0, -5,
+    -5, 0, 0, 0, 0, 0, 0, -5,
+    0, 0, 0, 5, 5, 0, 0, 0,
+];
+// Rooks are just as strong anywhere once the board opens up, apart from cutting off the king
+#[rustfmt::skip]
+const ROOK_ENDGAME: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0,
+    10, 10, 10, 10, 10, 10, 10, 10,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+#[rustfmt::skip]
+const QUEEN_MIDDLEGAME: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -10, 0, 5, 5, 5, 5, 0, -10,
+    -5, 0, 5, 5, 5, 5, 0, -5,
+    0, 0, 5, 5, 5, 5, 0, -5,
+    -10, 5, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 0, 0, 0, 0, -10,
+    -20, -10, -10, -5, -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const QUEEN_ENDGAME: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10,
+    -5, 0, 10, 15, 15, 10, 0, -5,
+    -5, 0, 10, 15, 15, 10, 0, -5,
+    -10, 0, 5, 10, 10, 5, 0, -10,
+    -10, 0, 0, 0, 0, 0, 0, -10,
+    -20, -10, -10, -5, -5, -10, -10, -20,
+];
+// Stay castled behind the pawns while there are enough pieces left to attack the king
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+    20, 20, 0, 0, 0, 0, 20, 20,
+    20, 30, 10, 0, 0, 10, 30, 20,
+];
+// Then head for the centre to support the pawns
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10, 0, 0, -10, -20, -30,
+    -30, -10, 20, 30, 30, 20, -10, -30,
+    -30, -10, 30, 40, 40, 30, -10, -30,
+    -30, -10, 30, 40, 40, 30, -10, -30,
+    -30, -10, 20, 30, 30, 20, -10, -30,
+    -30, -30, 0, 0, 0, 0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// The middlegame and endgame tables for a piece
+fn get_tables(piece: Piece) -> (&'static [i32; 64], &'static [i32; 64]) {
+    match piece {
+        Piece::Pawn => (&PAWN_MIDDLEGAME, &PAWN_ENDGAME),
+        Piece::Knight => (&KNIGHT_MIDDLEGAME, &KNIGHT_ENDGAME),
+        Piece::Bishop => (&BISHOP_MIDDLEGAME, &BISHOP_ENDGAME),
+        Piece::Rook => (&ROOK_MIDDLEGAME, &ROOK_ENDGAME),
+        Piece::Queen => (&QUEEN_MIDDLEGAME, &QUEEN_ENDGAME),
+        Piece::King => (&KING_MIDDLEGAME, &KING_ENDGAME),
+        Piece::Empty => panic!("no piece square table for an empty square"),
+    }
+}
+
+// The bonus for a piece of the given color standing on the given index
+pub fn get_piece_square_value(color: Color, piece: Piece, index: usize) -> TaperedScore {
+    // Flipping the rank turns a Black square into the matching White square
+    let index = match color {
+        Color::White => index,
+        _ => index ^ 56,
+    };
+    let (middlegame, endgame) = get_tables(piece);
+    TaperedScore::new(middlegame[index], endgame[index])
+}
+
+// The sum of the bonuses of all pieces of a color and kind
+pub fn get_piece_square_eval(board: &Board, color: Color, piece: Piece) -> TaperedScore {
+    let mut bits = board.get_color_bitmask(color) & board.get_piece_bitmask(piece);
+    let mut eval = TaperedScore::default();
+    while bits != 0 {
+        eval += get_piece_square_value(color, piece, bits.trailing_zeros() as usize);
+        bits &= bits - 1;
+    }
+    eval
+}
+
+#[cfg(test)]
+mod piece_square_tests {
+    use super::*;
+
+    #[test]
+    fn pawns_on_back_ranks_are_neutral() {
+        for index in (0..8).chain(56..64) {
+            assert_eq!(
+                get_piece_square_value(Color::White, Piece::Pawn, index),
+                TaperedScore::default()
+            );
+        }
+    }
+
+    #[test]
+    fn knights_belong_in_the_centre() {
+        // e4 against a1
+        let centre = get_piece_square_value(Color::White, Piece::Knight, 36);
+        let corner = get_piece_square_value(Color::White, Piece::Knight, 56);
+        assert!(centre.middlegame > corner.middlegame);
+        assert!(centre.endgame > corner.endgame);
+    }
+
+    #[test]
+    fn king_shelters_then_centralises() {
+        // g1 against e4
+        let castled = get_piece_square_value(Color::White, Piece::King, 62);
+        let centre = get_piece_square_value(Color::White, Piece::King, 36);
+        assert!(castled.middlegame > centre.middlegame);
+        assert!(castled.endgame < centre.endgame);
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+use crate::board::types::{Board, Piece};
+
+// The game phase with all pieces on the board. Each knight or bishop counts 1, each rook 2 and
+// each queen 4, so the phase drops to 0 once only kings and pawns are left.
+pub const MAX_PHASE: i32 = 24;
+
+// An evaluation term with separate values for the middlegame and the endgame, blended by the game
+// phase
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaperedScore {
+    pub middlegame: i32,
+    pub endgame: i32,
+}
+
+impl TaperedScore {
+    pub const fn new(middlegame: i32, endgame: i32) -> TaperedScore {
+        TaperedScore {
+            middlegame,
+            endgame,
+        }
+    }
+
+    // Interpolates linearly, from the middlegame value at MAX_PHASE to the endgame value at 0
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = TaperedScore;
+
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(
+            self.middlegame + other.middlegame,
+            self.endgame + other.endgame,
+        )
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: TaperedScore) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = TaperedScore;
+
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        self + -other
+    }
+}
+
+impl Mul<i32> for TaperedScore {
+    type Output = TaperedScore;
+
+    fn mul(self, factor: i32) -> TaperedScore {
+        TaperedScore::new(self.middlegame * factor, self.endgame * factor)
+    }
+}
+
+impl Neg for TaperedScore {
+    type Output = TaperedScore;
+
+    fn neg(self) -> TaperedScore {
+        TaperedScore::new(-self.middlegame, -self.endgame)
+    }
+}
+
+// How much non-pawn material is left, from MAX_PHASE in the opening down to 0. Promotions can push
+// the count above MAX_PHASE, so it is capped there.
+pub fn get_game_phase(board: &Board) -> i32 {
+    let count = |piece| board.get_piece_bitmask(piece).count_ones() as i32;
+    let phase = count(Piece::Knight)
+        + count(Piece::Bishop)
+        + 2 * count(Piece::Rook)
+        + 4 * count(Piece::Queen);
+    phase.min(MAX_PHASE)
+}
+
+#[cfg(test)]
+mod tapered_tests {
+    use super::*;
+    use crate::board::fen_util::get_game_state_from_fen;
+    use crate::board::GameState;
+
+    #[test]
+    fn taper() {
+        let score = TaperedScore::new(100, -20);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), -20);
+        assert_eq!(score.taper(MAX_PHASE / 2), 40);
+        // Rounds towards zero, so negating a score negates its tapered value
+        assert_eq!(TaperedScore::new(1, 0).taper(1), 0);
+        assert_eq!(
+            (-TaperedScore::new(7, 3)).taper(5),
+            -TaperedScore::new(7, 3).taper(5)
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut score = TaperedScore::new(10, 20) + TaperedScore::new(1, 2);
+        assert_eq!(score, TaperedScore::new(11, 22));
+        score += TaperedScore::new(-11, 0);
+        assert_eq!(score, TaperedScore::new(0, 22));
+        assert_eq!(score - TaperedScore::new(5, 5), TaperedScore::new(-5, 17));
+        assert_eq!(score * 3, TaperedScore::new(0, 66));
+    }
+
+    #[test]
+    fn game_phase() {
+        assert_eq!(get_game_phase(&GameState::default().board), MAX_PHASE);
+        let phase = |fen| get_game_phase(&get_game_state_from_fen(fen).unwrap().board);
+        assert_eq!(phase("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"), 0);
+        assert_eq!(phase("r3k3/8/8/8/8/8/8/2B1KN2 w - - 0 1"), 4);
+        assert_eq!(phase("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1"), 8);
+        // Three queens and all the other pieces
+        assert_eq!(
+            phase("rnbqkbnr/qqpppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            MAX_PHASE
+        );
+    }
+}
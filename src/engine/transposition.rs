@@ -0,0 +1,146 @@
+use std::mem::size_of;
+
+use crate::board::types::Move;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    // The score is the exact value of the position
+    Exact,
+    // The search failed high, so the real score is at least this value
+    Lower,
+    // The search failed low, so the real score is at most this value
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranspositionEntry {
+    pub hash: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+}
+
+// Fixed-size hash table of previously searched positions, indexed by the Zobrist hash
+pub struct TranspositionTable {
+    entries: Vec<Option<TranspositionEntry>>,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_SIZE_MB)
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let entry_count = (size_mb * 1024 * 1024 / size_of::<Option<TranspositionEntry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; entry_count],
+        }
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TranspositionTable::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<&TranspositionEntry> {
+        self.entries[self.get_index(hash)]
+            .as_ref()
+            .filter(|entry| entry.hash == hash)
+    }
+
+    // Replaces the existing entry unless it is a deeper search of the same position
+    pub fn store(&mut self, entry: TranspositionEntry) {
+        let index = self.get_index(entry.hash);
+        let slot = &mut self.entries[index];
+        if let Some(existing) = slot {
+            if existing.hash == entry.hash && existing.depth > entry.depth {
+                return;
+            }
+        }
+        *slot = Some(entry);
+    }
+
+    fn get_index(&self, hash: u64) -> usize {
+        (hash % self.capacity() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod transposition_tests {
+    use super::*;
+
+    fn entry(hash: u64, depth: u32, score: i32) -> TranspositionEntry {
+        TranspositionEntry {
+            hash,
+            depth,
+            bound: Bound::Exact,
+            score,
+            best_move: Some(Move::new(52, 36)),
+        }
+    }
+
+    #[test]
+    fn size_in_megabytes() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(
+            table.capacity(),
+            1024 * 1024 / size_of::<Option<TranspositionEntry>>()
+        );
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(12345, 3, 42));
+        assert_eq!(table.probe(12345), Some(&entry(12345, 3, 42)));
+        assert_eq!(table.probe(54321), None);
+    }
+
+    #[test]
+    fn probe_checks_full_hash() {
+        let mut table = TranspositionTable::new(1);
+        let colliding_hash = 12345 + table.capacity() as u64;
+        table.store(entry(12345, 3, 42));
+        assert_eq!(table.probe(colliding_hash), None);
+    }
+
+    #[test]
+    fn keeps_deeper_entry_for_same_position() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(12345, 5, 42));
+        table.store(entry(12345, 2, 7));
+        assert_eq!(table.probe(12345).unwrap().depth, 5);
+        table.store(entry(12345, 6, 7));
+        assert_eq!(table.probe(12345).unwrap().depth, 6);
+    }
+
+    #[test]
+    fn replaces_other_position() {
+        let mut table = TranspositionTable::new(1);
+        let colliding_hash = 12345 + table.capacity() as u64;
+        table.store(entry(12345, 5, 42));
+        table.store(entry(colliding_hash, 1, 7));
+        assert_eq!(table.probe(12345), None);
+        assert!(table.probe(colliding_hash).is_some());
+    }
+
+    #[test]
+    fn clear() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(12345, 3, 42));
+        table.clear();
+        assert_eq!(table.probe(12345), None);
+    }
+}
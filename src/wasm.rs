@@ -0,0 +1,281 @@
+// The JavaScript interface used by the web UI. Game states are passed back and forth as JSON.
+
+use crate::board::fen_util::{game_state_to_fen_string, get_validated_game_state_from_fen};
+use crate::board::game::Game;
+use crate::board::pgn::PgnGame;
+use crate::board::types::*;
+use crate::board::validation::PositionProblem;
+use crate::board::GameState;
+use crate::engine::generate::search;
+use crate::engine::limits::SearchLimits;
+use crate::engine::transposition::TranspositionTable;
+use gloo_utils::format::JsValueSerdeExt;
+use serde::Deserialize;
+use serde::Serialize;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+// A macro to provide `println!(..)`-style syntax for `console.log` logging.
+#[macro_export]
+macro_rules! log {
+    ( $( $t:tt )* ) => {
+        web_sys::console::log_1(&format!( $( $t )* ).into());
+    }
+}
+
+// How long the engine thinks about each move in the web UI
+const ENGINE_MOVE_TIME_MS: u64 = 1000;
+
+thread_local! {
+    // Kept between engine moves so that later searches can reuse earlier results
+    static TRANSPOSITION_TABLE: RefCell<TranspositionTable> = RefCell::new(TranspositionTable::default());
+}
+
+#[wasm_bindgen]
+pub fn new_game() {
+    TRANSPOSITION_TABLE.with_borrow_mut(|table| table.clear());
+}
+
+#[wasm_bindgen]
+pub fn set_hash_size(size_mb: usize) {
+    TRANSPOSITION_TABLE.with_borrow_mut(|table| table.resize(size_mb));
+}
+
+// A game kept in WASM memory, so that only the moves and positions JavaScript asks for cross the
+// boundary
+#[wasm_bindgen]
+pub struct GameSession {
+    game: Game,
+}
+
+impl Default for GameSession {
+    fn default() -> Self {
+        GameSession::new()
+    }
+}
+
+#[wasm_bindgen]
+impl GameSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GameSession {
+        GameSession {
+            game: Game::default(),
+        }
+    }
+
+    pub fn from_fen(fen: &str) -> Result<GameSession, JsError> {
+        let start = get_validated_game_state_from_fen(fen)
+            .map_err(|error| JsError::new(&format!("{} in '{}'", error, fen)))?;
+        Ok(GameSession {
+            game: Game::new(start),
+        })
+    }
+
+    pub fn game_state(&self) -> JsValue {
+        JsValue::from_serde(self.game.current()).unwrap()
+    }
+
+    // The moves leading to the current position
+    pub fn moves(&self) -> JsValue {
+        JsValue::from_serde(self.game.moves()).unwrap()
+    }
+
+    // Every move, including ones that were taken back and can be redone
+    pub fn all_moves(&self) -> JsValue {
+        JsValue::from_serde(self.game.all_moves()).unwrap()
+    }
+
+    pub fn ply(&self) -> usize {
+        self.game.ply()
+    }
+
+    pub fn legal_moves(&self) -> JsValue {
+        JsValue::from_serde(&self.game.current().generate_legal_moves()).unwrap()
+    }
+
+    // Plays a move given in UCI notation and returns it
+    pub fn play(&mut self, uci: &str) -> Result<JsValue, JsError> {
+        let next_move = self.game.current().parse_uci_move(uci)?;
+        self.game.play_move(next_move);
+        Ok(JsValue::from_serde(&next_move).unwrap())
+    }
+
+    // Takes back the last move, returning false if there was none
+    pub fn undo(&mut self) -> bool {
+        self.game.undo_move().is_some()
+    }
+
+    // Plays the last move that was taken back again, returning false if there was none
+    pub fn redo(&mut self) -> bool {
+        self.game.redo_move().is_some()
+    }
+
+    // Steps to the position after the given number of moves, returning false if there are not
+    // that many
+    pub fn go_to_ply(&mut self, ply: usize) -> bool {
+        self.game.go_to_ply(ply)
+    }
+
+    pub fn fen(&self) -> String {
+        game_state_to_fen_string(self.game.current())
+    }
+
+    pub fn pgn(&self) -> String {
+        PgnGame::from_game(&self.game).to_pgn()
+    }
+
+    pub fn status(&self) -> JsValue {
+        JsValue::from_serde(&self.game.status()).unwrap()
+    }
+
+    // Searches the current position within the given SearchLimits, or for ENGINE_MOVE_TIME_MS if
+    // there are none, and plays the best move. Returns the move, or null if the game is over.
+    pub fn engine_move(&mut self, limits: JsValue) -> Result<JsValue, JsError> {
+        let limits = if limits.is_undefined() || limits.is_null() {
+            SearchLimits::move_time(ENGINE_MOVE_TIME_MS)
+        } else {
+            limits.into_serde()?
+        };
+        Ok(JsValue::from_serde(&self.play_engine_move(&limits)).unwrap())
+    }
+}
+
+impl GameSession {
+    fn from_game_state(game_state: JsValue) -> GameSession {
+        GameSession {
+            game: Game::new(game_state.into_serde().unwrap()),
+        }
+    }
+
+    fn play_move(&mut self, next_move: Move) -> Result<(), JsError> {
+        if !self.game.current().is_legal(next_move) {
+            return Err(JsError::new(&format!(
+                "illegal move {}",
+                self.game.current().move_to_uci(next_move)
+            )));
+        }
+        self.game.play_move(next_move);
+        Ok(())
+    }
+
+    fn play_engine_move(&mut self, limits: &SearchLimits) -> Option<Move> {
+        let next_move = TRANSPOSITION_TABLE
+            .with_borrow_mut(|table| search(self.game.current(), limits, table).best_move);
+        if let Some(next_move) = next_move {
+            self.game.play_move(next_move);
+        }
+        next_move
+    }
+}
+
+// The functions below take and return the whole game state as JSON. They predate GameSession and
+// are kept for compatibility.
+
+#[wasm_bindgen]
+pub fn get_game_state_from_fen(fen: &str) -> Result<JsValue, JsError> {
+    Ok(GameSession::from_fen(fen)?.game_state())
+}
+
+// Problems that make the position unplayable, for the board editor to show while it is being set up
+#[wasm_bindgen]
+pub fn validate_game_state(game_state: JsValue) -> JsValue {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let problems: Vec<(PositionProblem, String)> = game_state
+        .validate()
+        .into_iter()
+        .map(|problem| (problem, problem.to_string()))
+        .collect();
+    JsValue::from_serde(&problems).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_initial_game_state() -> JsValue {
+    GameSession::new().game_state()
+}
+
+#[wasm_bindgen]
+pub fn convert_game_state_to_squares(game_state: JsValue) -> JsValue {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let squares: Vec<(Color, Piece)> = (0..64)
+        .map(|index| game_state.board.get_square(index))
+        .collect();
+    JsValue::from_serde(&squares).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_square_at_index(game_state: JsValue, index: usize) -> JsValue {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let square = game_state.board.get_square(index);
+    JsValue::from_serde(&square).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_legal_moves(game_state: JsValue) -> JsValue {
+    GameSession::from_game_state(game_state).legal_moves()
+}
+
+#[wasm_bindgen]
+pub fn get_next_legal_moves(game_state: JsValue) -> JsValue {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let moves = game_state.generate_legal_states();
+    JsValue::from_serde(&moves).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn perform_move(game_state: JsValue, next_move: JsValue) -> Result<JsValue, JsError> {
+    let mut session = GameSession::from_game_state(game_state);
+    session.play_move(next_move.into_serde().unwrap())?;
+    Ok(session.game_state())
+}
+
+#[wasm_bindgen]
+pub fn get_move_san(game_state: JsValue, next_move: JsValue) -> String {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let next_move: Move = next_move.into_serde().unwrap();
+    game_state.move_to_display_san(next_move)
+}
+
+#[wasm_bindgen]
+pub fn parse_san_move(game_state: JsValue, san: &str) -> Result<JsValue, JsError> {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let next_move = game_state.parse_san(san)?;
+    Ok(JsValue::from_serde(&next_move).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn get_game_status(start_game_state: JsValue, moves: JsValue) -> JsValue {
+    let mut session = GameSession::from_game_state(start_game_state);
+    let moves: Vec<Move> = moves.into_serde().unwrap();
+    for next_move in moves {
+        session.game.play_move(next_move);
+    }
+    session.status()
+}
+
+#[wasm_bindgen]
+pub struct InCheckReturn(pub bool, pub usize);
+
+#[wasm_bindgen]
+pub fn in_check(game_state: JsValue) -> InCheckReturn {
+    let game_state: GameState = game_state.into_serde().unwrap();
+    let in_check = game_state.is_in_check();
+    let king_index = game_state.board.find_king(game_state.turn);
+    InCheckReturn(in_check, king_index)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameStateAndEngineMove {
+    game_state: GameState,
+    next_move: Option<Move>,
+}
+
+#[wasm_bindgen]
+pub fn get_best_engine_move(game_state: JsValue) -> JsValue {
+    let mut session = GameSession::from_game_state(game_state);
+    let next_move = session.play_engine_move(&SearchLimits::move_time(ENGINE_MOVE_TIME_MS));
+    let game_state_and_engine_move = GameStateAndEngineMove {
+        game_state: session.game.current().clone(),
+        next_move,
+    };
+    JsValue::from_serde(&game_state_and_engine_move).unwrap()
+}
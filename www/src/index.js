@@ -31,8 +31,23 @@ const SQUARE_IMAGE_MAP = {
   },
 };
 
+const DRAW_REASONS = {
+  Stalemate: 'stalemate',
+  FiftyMoveRule: 'no capture or pawn move in 50 moves',
+  SeventyFiveMoveRule: 'no capture or pawn move in 75 moves',
+  ThreefoldRepetition: 'the same position occurred three times',
+  FivefoldRepetition: 'the same position occurred five times',
+  InsufficientMaterial: 'neither side can checkmate',
+};
+
+// Draws that only end the game when the player claims them
+const CLAIMABLE_DRAWS = ['FiftyMoveRule', 'ThreefoldRepetition'];
+
+wasm.new_game();
 let gameState = wasm.get_initial_game_state();
-let nextLegalGameStates = wasm.get_pseudo_legal_moves(gameState);
+const startGameState = gameState;
+const moveHistory = [];
+let nextLegalMoves = wasm.get_legal_moves(gameState);
 
 wasm
   .convert_game_state_to_squares(gameState)
@@ -98,6 +113,15 @@ function updateBoard(move) {
   }
 }
 
+function addMoveToList(move) {
+  const san = wasm.get_move_san(gameState, move);
+  const moveList = document.querySelector('#moveList');
+  if (gameState.turn === 'White') {
+    moveList.appendChild(document.createElement('li'));
+  }
+  moveList.lastChild.append(`${san} `);
+}
+
 function getPieceImage(color, piece) {
   const img = document.createElement('img');
   img.src = SQUARE_IMAGE_MAP[color][piece];
@@ -125,7 +149,7 @@ function getOnClick(index) {
       selectedPiece != null &&
       validTargetSquares.includes(index)
     ) {
-      const nextMoves = nextLegalGameStates.filter((move) => {
+      const nextMoves = nextLegalMoves.filter((move) => {
         return move.from === selectedPiece && move.to === index;
       });
       if (nextMoves.length > 1) {
@@ -150,8 +174,10 @@ function performMove(move) {
     return;
   }
   updateBoard(move);
+  addMoveToList(move);
+  moveHistory.push(move);
   gameState = wasm.perform_move(gameState, move);
-  nextLegalGameStates = wasm.get_pseudo_legal_moves(gameState);
+  nextLegalMoves = wasm.get_legal_moves(gameState);
   selectedPiece = null;
   validTargetSquares = null;
   checkForWinLoseDraw();
@@ -164,28 +190,40 @@ function performComputerMove() {
   }
   const { game_state: newGameState, next_move: move } =
     wasm.get_best_engine_move(gameState);
+  if (move) {
+    addMoveToList(move);
+    moveHistory.push(move);
+  }
   gameState = newGameState;
-  nextLegalGameStates = wasm.get_pseudo_legal_moves(gameState);
+  nextLegalMoves = wasm.get_legal_moves(gameState);
   updateBoard(move);
+  checkForWinLoseDraw();
 }
 
 function checkForWinLoseDraw() {
-  if (gameState.halfmove_counter >= 100) {
-    gameOver = true;
-    alert("No capture or pawn move in 50 moves. It's a draw! :/");
-  } else if (nextLegalGameStates.length === 0) {
-    gameOver = true;
-    const inCheckReturn = wasm.in_check(gameState);
-    const inCheck = inCheckReturn[0];
-    if (inCheck) {
-      if (gameState.turn === 'White') {
-        alert('You lose :(');
-      } else {
-        alert('You win! :D');
-      }
+  if (gameOver) {
+    return;
+  }
+  const status = wasm.get_game_status(startGameState, moveHistory);
+  if (status === 'Ongoing') {
+    return;
+  }
+  if (
+    CLAIMABLE_DRAWS.includes(status) &&
+    (gameState.turn !== playerColor ||
+      !confirm(`Claim a draw? (${DRAW_REASONS[status]})`))
+  ) {
+    return;
+  }
+  gameOver = true;
+  if (status.Checkmate) {
+    if (status.Checkmate === playerColor) {
+      alert('You win! :D');
     } else {
-      alert("It's a draw! :/");
+      alert('You lose :(');
     }
+  } else {
+    alert(`It's a draw! :/ (${DRAW_REASONS[status]})`);
   }
 }
 
@@ -259,7 +297,7 @@ function updateCellClasses(
 function updateSelectedPiece(index) {
   selectedPiece = index;
   validTargetSquares = [];
-  nextLegalGameStates.forEach((move) => {
+  nextLegalMoves.forEach((move) => {
     if (move.from === index) {
       validTargetSquares.push(move.to);
     }